@@ -0,0 +1,177 @@
+use std::io::Write;
+use std::process::{Command, Stdio};
+use std::{env, fs, io, path};
+
+use super::tasks::TaskerError;
+
+/// # The places copied text can be sent to
+/// Which one gets used comes from the `TASKER_CLIPBOARD` environment variable, see
+/// [`Clipboard::from_env`]. The terminal escape works over SSH, while the file and stdout
+/// backends are there for scripts and machines without a display server.
+#[derive(Debug, PartialEq)]
+pub enum Clipboard {
+    /// Asks the terminal to set the clipboard with an OSC 52 escape sequence
+    Osc52,
+    /// Pipes the text into a clipboard program such as `xclip` or `wl-copy`
+    Program(String, Vec<String>),
+    /// Writes the text to a file, replacing what was there
+    File(path::PathBuf),
+    /// Prints the text to standard output
+    Stdout,
+}
+
+impl Clipboard {
+    /// # Picks the clipboard to use
+    /// `TASKER_CLIPBOARD` can be set to `osc52`, `xclip`, `xsel`, `wl-copy`, `pbcopy`, `stdout`
+    /// or `file:<path>`. When it isn't set, we go by the display server that's running, and
+    /// fall back to the terminal escape if there isn't one.
+    pub fn from_env() -> Clipboard {
+        if let Ok(name) = env::var("TASKER_CLIPBOARD") {
+            if let Some(clipboard) = Clipboard::parse(&name) {
+                return clipboard;
+            }
+        }
+
+        if env::var_os("WAYLAND_DISPLAY").is_some() {
+            Clipboard::parse("wl-copy").unwrap()
+        } else if env::var_os("DISPLAY").is_some() {
+            Clipboard::parse("xclip").unwrap()
+        } else if cfg!(target_os = "macos") {
+            Clipboard::parse("pbcopy").unwrap()
+        } else {
+            Clipboard::Osc52
+        }
+    }
+
+    pub fn parse(name: &str) -> Option<Clipboard> {
+        let program = |command: &str, args: &[&str]| {
+            let args = args.iter().map(|arg| arg.to_string()).collect();
+            Some(Clipboard::Program(command.into(), args))
+        };
+
+        match name {
+            "osc52" => Some(Clipboard::Osc52),
+            "stdout" => Some(Clipboard::Stdout),
+            "xclip" => program("xclip", &["-selection", "clipboard"]),
+            "xsel" => program("xsel", &["--clipboard", "--input"]),
+            "wl-copy" => program("wl-copy", &[]),
+            "pbcopy" => program("pbcopy", &[]),
+            _ => name
+                .strip_prefix("file:")
+                .map(|file| Clipboard::File(file.into())),
+        }
+    }
+
+    pub fn copy(&self, text: &str) -> Result<(), TaskerError> {
+        let (target, result): (path::PathBuf, io::Result<()>) = match self {
+            Clipboard::Osc52 => ("the terminal".into(), write_osc52(text)),
+            Clipboard::Program(command, args) => {
+                (command.into(), pipe_to_program(command, args, text))
+            }
+            Clipboard::File(file) => (file.clone(), fs::write(file, text)),
+            Clipboard::Stdout => ("stdout".into(), writeln!(io::stdout(), "{}", text)),
+        };
+
+        result.map_err(|error| TaskerError::io(target, error))
+    }
+}
+
+/// The escape goes straight to the terminal when we can get at it, so that it still works
+/// when our output is being piped somewhere.
+fn write_osc52(text: &str) -> io::Result<()> {
+    let sequence = format!("\x1b]52;c;{}\x07", base64(text.as_bytes()));
+
+    match fs::OpenOptions::new().write(true).open("/dev/tty") {
+        Ok(mut terminal) => terminal.write_all(sequence.as_bytes()),
+        Err(_) => io::stdout().write_all(sequence.as_bytes()),
+    }
+}
+
+fn pipe_to_program(command: &str, args: &[String], text: &str) -> io::Result<()> {
+    let mut child = Command::new(command)
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .spawn()
+        .map_err(|error| match error.kind() {
+            io::ErrorKind::NotFound => io::Error::other(format!("{} couldn't be found", command)),
+            _ => error,
+        })?;
+
+    child
+        .stdin
+        .take()
+        .expect("Clipboard program has no stdin!")
+        .write_all(text.as_bytes())?;
+
+    let status = child.wait()?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(io::Error::other(format!(
+            "{} exited with {}",
+            command, status
+        )))
+    }
+}
+
+fn base64(input: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut output = String::with_capacity(input.len().div_ceil(3) * 4);
+
+    for chunk in input.chunks(3) {
+        let bytes = [
+            chunk[0],
+            *chunk.get(1).unwrap_or(&0),
+            *chunk.get(2).unwrap_or(&0),
+        ];
+        let group = (bytes[0] as u32) << 16 | (bytes[1] as u32) << 8 | bytes[2] as u32;
+
+        for position in 0..4 {
+            if position <= chunk.len() {
+                let index = (group >> (18 - position * 6)) & 0b11_1111;
+                output.push(ALPHABET[index as usize] as char);
+            } else {
+                output.push('=');
+            }
+        }
+    }
+
+    output
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_base64_padding() {
+        assert_eq!(base64(b""), "");
+        assert_eq!(base64(b"f"), "Zg==");
+        assert_eq!(base64(b"fo"), "Zm8=");
+        assert_eq!(base64(b"foo"), "Zm9v");
+        assert_eq!(
+            base64("Merge PR #42\n✓".as_bytes()),
+            "TWVyZ2UgUFIgIzQyCuKckw=="
+        );
+    }
+
+    #[test]
+    fn test_parse_backends() {
+        assert_eq!(Clipboard::parse("osc52"), Some(Clipboard::Osc52));
+        assert_eq!(
+            Clipboard::parse("file:/tmp/copied"),
+            Some(Clipboard::File("/tmp/copied".into()))
+        );
+        assert_eq!(Clipboard::parse("carrier-pigeon"), None);
+    }
+
+    #[test]
+    fn test_copy_to_file() {
+        let file = env::temp_dir().join(format!("tasker-clipboard-{}", std::process::id()));
+        Clipboard::File(file.clone()).copy("one\ntwo").unwrap();
+
+        assert_eq!(fs::read_to_string(&file).unwrap(), "one\ntwo");
+        fs::remove_file(file).unwrap();
+    }
+}
@@ -0,0 +1,375 @@
+use std::{fs, io, path};
+
+use chrono::Local;
+use colored::*;
+
+use super::parse::*;
+use super::persist;
+use super::tasks::{check_mark, Journal, TaskerError};
+use super::{Settings, TaskList};
+
+/// # What was done to one of our files
+struct Diagnosis {
+    location: path::PathBuf,
+    /// Why the file needed fixing, if it did
+    problem: Option<String>,
+    changes: Vec<String>,
+}
+
+impl Diagnosis {
+    fn new(location: &path::Path) -> Diagnosis {
+        Diagnosis {
+            location: location.to_path_buf(),
+            problem: None,
+            changes: Vec::new(),
+        }
+    }
+
+    fn render(&self) -> String {
+        let mut output = match &self.problem {
+            None if self.changes.is_empty() => {
+                format!(" {} {} is fine", check_mark(), self.location.display())
+            }
+            None => format!(" {} {} was repaired", "!".yellow(), self.location.display()),
+            Some(problem) => format!(" {} {} {}", "!".yellow(), self.location.display(), problem),
+        };
+
+        for change in &self.changes {
+            output = format!("{}\n    {}", output, change);
+        }
+        output
+    }
+}
+
+/// # Checks and repairs every file Tasker keeps
+/// This works even when the files can't be loaded, which is when it's needed most. Damaged
+/// files are kept beside the repaired ones with a `.broken-<time>` suffix, and a report of
+/// what changed is written to the data directory. Repairs aren't recorded in the journal, so
+/// those copies are the only way back.
+pub fn run_doctor() -> Result<String, TaskerError> {
+    let stamp = Local::now().format("%Y%m%d-%H%M%S%.3f").to_string();
+    let mut diagnoses: Vec<Diagnosis> = Vec::new();
+
+    // The settings say where the lock is, so they're only read until it's held
+    let settings_location = get_settings_location();
+    let settings = read_if_present(&settings_location)?
+        .and_then(|contents| Settings::new(contents).ok())
+        .unwrap_or_else(|| Settings::new(String::new()).unwrap());
+    create_data_dir(&settings)?;
+    let _lock = lock_data_dir(&settings)?;
+
+    let (settings, diagnosis) = check_settings(&settings_location, &stamp)?;
+    diagnoses.push(diagnosis);
+    for location in [
+        get_task_list_location(&settings),
+        get_archive_location(&settings),
+    ] {
+        diagnoses.push(check_task_list_file(&location, &stamp)?);
+    }
+    diagnoses.push(check_journal_file(
+        &get_journal_location(&settings),
+        &stamp,
+    )?);
+
+    let report: Vec<String> = diagnoses.iter().map(Diagnosis::render).collect();
+    let mut output = report.join("\n");
+
+    if diagnoses
+        .iter()
+        .any(|diagnosis| !diagnosis.changes.is_empty())
+    {
+        let report_location = get_data_location(&settings).join("doctor-report.txt");
+        let plain: Vec<String> = report.iter().map(|line| strip_colors(line)).collect();
+        let contents = format!("ts doctor, {}\n\n{}\n", stamp, plain.join("\n"));
+
+        persist::replace(&report_location, &contents)
+            .map_err(|error| TaskerError::io(&report_location, error))?;
+        output = format!(
+            "{}\n Report written to {}",
+            output,
+            report_location.display().to_string().dimmed()
+        );
+    }
+
+    Ok(output)
+}
+
+/// Damaged settings are swapped for the defaults, there's nothing in them worth salvaging.
+fn check_settings(
+    location: &path::Path,
+    stamp: &str,
+) -> Result<(Settings, Diagnosis), TaskerError> {
+    let mut diagnosis = Diagnosis::new(location);
+
+    let contents = match read_if_present(location)? {
+        Some(contents) => contents,
+        None => return Ok((Settings::new(String::new()).unwrap(), diagnosis)),
+    };
+
+    match Settings::new(contents) {
+        Ok(settings) => Ok((settings, diagnosis)),
+        Err(error) => {
+            let settings = Settings::new(String::new()).unwrap();
+            diagnosis.problem = Some(format!("couldn't be parsed: {}", error));
+            diagnosis.changes.push(keep_broken_copy(location, stamp)?);
+            diagnosis.changes.push("reset to the defaults".into());
+
+            persist::replace(location, &settings.to_string())
+                .map_err(|error| TaskerError::io(location, error))?;
+            Ok((settings, diagnosis))
+        }
+    }
+}
+
+/// # Checks a task list file, fixing whatever it can
+/// Lists that don't parse have every readable item salvaged, with the unreadable ones taken
+/// from the backup where possible. If the file isn't JSON at all, the backup is restored
+/// instead. An empty file is never written by Tasker, even an empty list is `{}`, so one with
+/// a backup beside it is restored too. Whatever comes out of that is then validated and
+/// repaired like any loaded list.
+fn check_task_list_file(location: &path::Path, stamp: &str) -> Result<Diagnosis, TaskerError> {
+    let mut diagnosis = Diagnosis::new(location);
+
+    let contents = match read_if_present(location)? {
+        Some(contents) => contents,
+        None => return Ok(diagnosis),
+    };
+
+    let mut task_list = match TaskList::new(contents.clone()) {
+        Ok(_) if contents.trim().is_empty() && persist::backup_location(location).exists() => {
+            diagnosis.problem = Some("is empty".into());
+            recover(location, &contents, &mut diagnosis.changes)?
+        }
+        Ok(task_list) => task_list,
+        Err(error) => {
+            diagnosis.problem = Some(format!("couldn't be parsed: {}", error));
+            recover(location, &contents, &mut diagnosis.changes)?
+        }
+    };
+
+    for issue in task_list.validate() {
+        diagnosis
+            .changes
+            .push(format!("repaired item {}, it {}", issue.id, issue.problem));
+    }
+    task_list.repair();
+
+    if diagnosis.problem.is_some() || !diagnosis.changes.is_empty() {
+        let broken = keep_broken_copy(location, stamp)?;
+        diagnosis.changes.insert(0, broken);
+        persist::replace(location, &task_list.to_string())
+            .map_err(|error| TaskerError::io(location, error))?;
+    }
+
+    Ok(diagnosis)
+}
+
+/// A damaged journal only costs the undo history, so it's started over rather than salvaged.
+fn check_journal_file(location: &path::Path, stamp: &str) -> Result<Diagnosis, TaskerError> {
+    let mut diagnosis = Diagnosis::new(location);
+
+    let contents = match read_if_present(location)? {
+        Some(contents) => contents,
+        None => return Ok(diagnosis),
+    };
+
+    if let Err(error) = Journal::new(contents) {
+        diagnosis.problem = Some(format!("couldn't be parsed: {}", error));
+        diagnosis.changes.push(keep_broken_copy(location, stamp)?);
+        diagnosis.changes.push("cleared the undo history".into());
+
+        let journal = Journal::new(String::new()).unwrap();
+        persist::replace(location, &journal.to_string())
+            .map_err(|error| TaskerError::io(location, error))?;
+    }
+    Ok(diagnosis)
+}
+
+fn recover(
+    location: &path::Path,
+    contents: &str,
+    changes: &mut Vec<String>,
+) -> Result<TaskList, TaskerError> {
+    let backup_location = persist::backup_location(location);
+    let backup: Option<TaskList> = read_if_present(&backup_location)?
+        .and_then(|backup_contents| TaskList::new(backup_contents).ok());
+
+    if let Some((task_list, notes)) = TaskList::salvage(contents, backup.as_ref()) {
+        changes.push("salvaged the items that could be read".into());
+        changes.extend(notes);
+        Ok(task_list)
+    } else if let Some(task_list) = backup {
+        changes.push(format!("restored {}", backup_location.display()));
+        Ok(task_list)
+    } else {
+        changes.push("nothing could be salvaged, and there's no usable backup".into());
+        Ok(TaskList::new(String::new()).unwrap())
+    }
+}
+
+fn read_if_present(location: &path::Path) -> Result<Option<String>, TaskerError> {
+    match fs::read_to_string(location) {
+        Ok(contents) => Ok(Some(contents)),
+        Err(error) if error.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(error) => Err(TaskerError::io(location, error)),
+    }
+}
+
+/// Copies a damaged file aside before it's replaced, so nothing is ever lost to a repair.
+fn keep_broken_copy(location: &path::Path, stamp: &str) -> Result<String, TaskerError> {
+    let broken = persist::sibling(location, "", &format!(".broken-{}", stamp));
+
+    fs::copy(location, &broken).map_err(|error| TaskerError::io(&broken, error))?;
+    Ok(format!("kept the original as {}", broken.display()))
+}
+
+/// The report file is plain text, so the terminal colours are taken back out.
+fn strip_colors(line: &str) -> String {
+    let mut output = String::with_capacity(line.len());
+    let mut chars = line.chars();
+
+    while let Some(character) = chars.next() {
+        if character == '\x1b' {
+            chars.by_ref().find(|next| next.is_ascii_alphabetic());
+        } else {
+            output.push(character);
+        }
+    }
+    output
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::env;
+
+    use crate::tasks::{item_json, list_json};
+    use serde_json::json;
+
+    /// A storage file in a directory of its own, with a backup holding items 0 and 1.
+    fn storage_with_backup(name: &str, contents: &str) -> path::PathBuf {
+        let directory =
+            env::temp_dir().join(format!("tasker-doctor-{}-{}", std::process::id(), name));
+        let _ = fs::remove_dir_all(&directory);
+        fs::create_dir_all(&directory).unwrap();
+        let location = directory.join("storage.json");
+
+        let backup = list_json([item_json(0, json!({})), item_json(1, json!({}))]);
+        fs::write(persist::backup_location(&location), backup).unwrap();
+        fs::write(&location, contents).unwrap();
+        location
+    }
+
+    /// The keys of the items in a list file.
+    fn ids_in(location: &path::Path) -> Vec<String> {
+        let contents = fs::read_to_string(location).unwrap();
+        let records: serde_json::Map<String, serde_json::Value> =
+            serde_json::from_str(&contents).unwrap();
+        records.keys().cloned().collect()
+    }
+
+    fn remove(location: &path::Path) {
+        fs::remove_dir_all(location.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn test_salvages_readable_items() {
+        let contents = json!({"0": item_json(0, json!({})), "1": {"_id": "one"}, "2": 5});
+        let contents = contents.to_string();
+        let location = storage_with_backup("salvage", &contents);
+
+        let diagnosis = check_task_list_file(&location, "stamp").unwrap();
+
+        assert!(diagnosis.problem.is_some());
+        assert!(diagnosis.changes[1].starts_with("salvaged"));
+        assert!(diagnosis.changes[2].starts_with("took item 1 from the backup"));
+        assert!(diagnosis.changes[3].starts_with("dropped item 2"));
+        assert_eq!(ids_in(&location), ["0", "1"]);
+        remove(&location);
+    }
+
+    #[test]
+    fn test_restores_the_backup() {
+        for (name, contents) in [
+            ("garbled", "not json"),
+            ("empty", ""),
+            ("cut", r#"{"0": {"#),
+        ] {
+            let location = storage_with_backup(name, contents);
+
+            let diagnosis = check_task_list_file(&location, "stamp").unwrap();
+
+            assert!(diagnosis.problem.is_some(), "{:?}", contents);
+            assert!(diagnosis.changes[1].starts_with("restored"));
+            assert_eq!(ids_in(&location), ["0", "1"]);
+            remove(&location);
+        }
+    }
+
+    #[test]
+    fn test_empty_file_without_backup_is_fine() {
+        let location = storage_with_backup("fresh", "");
+        fs::remove_file(persist::backup_location(&location)).unwrap();
+
+        let diagnosis = check_task_list_file(&location, "stamp").unwrap();
+
+        assert!(diagnosis.problem.is_none() && diagnosis.changes.is_empty());
+        assert_eq!(fs::read_to_string(&location).unwrap(), "");
+        remove(&location);
+    }
+
+    #[test]
+    fn test_recover_without_anything_usable() {
+        let location = storage_with_backup("nothing", "not json");
+        fs::write(persist::backup_location(&location), "not json either").unwrap();
+        let mut changes = Vec::new();
+
+        let task_list = recover(&location, "not json", &mut changes).unwrap();
+
+        assert_eq!(task_list.to_string(), "{}");
+        assert_eq!(
+            changes,
+            ["nothing could be salvaged, and there's no usable backup"]
+        );
+        remove(&location);
+    }
+
+    #[test]
+    fn test_damaged_journal_is_cleared() {
+        let storage = storage_with_backup("journal", "{}");
+        let location = storage.with_file_name("journal.json");
+        fs::write(&location, "garbage\n").unwrap();
+
+        let diagnosis = check_journal_file(&location, "stamp").unwrap();
+
+        assert!(diagnosis.problem.is_some());
+        assert_eq!(diagnosis.changes[1], "cleared the undo history");
+        assert!(Journal::new(fs::read_to_string(&location).unwrap()).is_ok());
+        assert!(check_journal_file(&location, "later")
+            .unwrap()
+            .problem
+            .is_none());
+        remove(&location);
+    }
+
+    #[test]
+    fn test_keeps_broken_copy() {
+        let location = storage_with_backup("broken", "not json");
+
+        let change = keep_broken_copy(&location, "stamp").unwrap();
+
+        let broken = location.with_file_name("storage.json.broken-stamp");
+        assert_eq!(change, format!("kept the original as {}", broken.display()));
+        assert_eq!(fs::read_to_string(broken).unwrap(), "not json");
+        remove(&location);
+    }
+
+    #[test]
+    fn test_strip_colors() {
+        assert_eq!(
+            strip_colors("\x1b[33m!\x1b[0m \x1b[1;31mstorage.json\x1b[0m was repaired"),
+            "! storage.json was repaired"
+        );
+        assert_eq!(strip_colors("plain ✓"), "plain ✓");
+    }
+}
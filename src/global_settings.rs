@@ -1,30 +1,57 @@
 use std::fmt;
 
 use serde::{Deserialize, Serialize};
-use serde_json;
 
 // I want to stay compatible with task book which uses camel Case.
 #[allow(non_snake_case)]
 #[derive(Deserialize, Serialize, Debug)]
 pub struct Settings {
-    taskbookDirectory: String,
+    /// Where the lists are kept, Tasker's config directory when it's not set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    taskbookDirectory: Option<String>,
     displayCompleteTasks: bool,
     displayProgressOverview: bool,
+    /// Taskbook's settings don't have this, so it's filled in for them
+    #[serde(default = "default_true")]
+    completeParentTasks: bool,
+    #[serde(default = "default_confirm_limit")]
+    confirmSelectionsOver: usize,
+}
+
+/// Older versions wrote this to every settings file without ever using it, so it doesn't count
+/// as a choice of directory.
+const UNUSED_DIRECTORY: &str = "~/.config/tasker";
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_confirm_limit() -> usize {
+    10
 }
 
 impl Settings {
     pub fn new(settings_file: String) -> Result<Settings, serde_json::Error> {
         if settings_file.is_empty() {
             Ok(Settings {
-                taskbookDirectory: "~/.config/tasker".into(),
+                taskbookDirectory: None,
                 displayCompleteTasks: true,
                 displayProgressOverview: true,
+                completeParentTasks: true,
+                confirmSelectionsOver: default_confirm_limit(),
             })
         } else {
             serde_json::from_str(&settings_file)
         }
     }
 
+    /// The directory the lists are kept in, if one was chosen.
+    pub fn directory(&self) -> Option<&str> {
+        self.taskbookDirectory
+            .as_deref()
+            .filter(|directory| *directory != UNUSED_DIRECTORY)
+    }
+
     pub fn show_completed(&self) -> bool {
         self.displayCompleteTasks
     }
@@ -32,6 +59,16 @@ impl Settings {
     pub fn show_progress(&self) -> bool {
         self.displayProgressOverview
     }
+
+    /// Whether checking the last subtask of a task checks the task too.
+    pub fn complete_parents(&self) -> bool {
+        self.completeParentTasks
+    }
+
+    /// How many items a selector like `3-7` or `@work` can pick out before asking first.
+    pub fn confirm_limit(&self) -> usize {
+        self.confirmSelectionsOver
+    }
 }
 
 impl fmt::Display for Settings {
@@ -49,9 +86,9 @@ mod test {
     use super::*;
 
     fn settings_string() -> String {
-        return "{\n\"taskbookDirectory\": \"~\",\n\"displayCompleteTasks\": true,\
+        "{\n\"taskbookDirectory\": \"~\",\n\"displayCompleteTasks\": true,\
             \"displayProgressOverview\": true\n}"
-            .into();
+            .into()
     }
 
     #[test]
@@ -59,6 +96,8 @@ mod test {
         let settings = Settings::new(settings_string()).unwrap();
         assert!(settings.show_completed());
         assert!(settings.show_progress());
+        assert!(settings.complete_parents());
+        assert_eq!(settings.confirm_limit(), 10);
     }
 
     #[test]
@@ -0,0 +1,144 @@
+use std::{fs, io, path};
+
+use colored::*;
+use serde::Deserialize;
+
+use super::parse::expand_home;
+use super::tasks::{check_mark, TaskerError};
+use super::TaskList;
+
+/// The parts of `~/.taskbook.json` we care about.
+#[allow(non_snake_case)]
+#[derive(Deserialize)]
+struct TaskbookConfig {
+    #[serde(default = "default_taskbook_directory")]
+    taskbookDirectory: String,
+}
+
+fn default_taskbook_directory() -> String {
+    "~".into()
+}
+
+/// # Finds where Taskbook keeps its items
+/// `directory` can either be Taskbook's own `.taskbook` directory, or the directory holding
+/// `.taskbook.json`, usually the home directory. In the second case the config is read to
+/// find the `taskbookDirectory` the items were stored under.
+pub fn find_taskbook_data(directory: &path::Path) -> Result<path::PathBuf, TaskerError> {
+    if directory.join("storage").is_dir() || directory.join("archive").is_dir() {
+        return Ok(directory.to_path_buf());
+    }
+
+    let config_location = directory.join(".taskbook.json");
+    let base = match fs::read_to_string(&config_location) {
+        Ok(contents) => match serde_json::from_str::<TaskbookConfig>(&contents) {
+            Ok(config) => expand_home(&config.taskbookDirectory),
+            Err(error) => return Err(TaskerError::parse(config_location, error)),
+        },
+        Err(error) if error.kind() == io::ErrorKind::NotFound => directory.to_path_buf(),
+        Err(error) => return Err(TaskerError::io(config_location, error)),
+    };
+
+    let data = base.join(".taskbook");
+    if data.is_dir() {
+        Ok(data)
+    } else {
+        let error = io::Error::new(io::ErrorKind::NotFound, "no Taskbook data was found");
+        Err(TaskerError::io(data, error))
+    }
+}
+
+/// # Loads one of Taskbook's item files
+/// A missing file is treated the same as an empty one, since Taskbook only makes the
+/// archive once something has been archived.
+fn load_taskbook_file(location: &path::Path) -> Result<TaskList, TaskerError> {
+    let contents = match fs::read_to_string(location) {
+        Ok(contents) => contents,
+        Err(error) if error.kind() == io::ErrorKind::NotFound => String::new(),
+        Err(error) => return Err(TaskerError::io(location, error)),
+    };
+
+    TaskList::new(contents).map_err(|error| TaskerError::parse(location, error))
+}
+
+/// # Merges Taskbook's items into ours
+/// Taskbook's storage goes into the task list and its archive into the archive. Nothing is
+/// changed unless both files could be read.
+pub fn import_taskbook(
+    directory: &path::Path,
+    task_list: &mut TaskList,
+    archive: &mut TaskList,
+) -> Result<String, TaskerError> {
+    let data = find_taskbook_data(directory)?;
+    let storage = load_taskbook_file(&data.join("storage").join("storage.json"))?;
+    let archived = load_taskbook_file(&data.join("archive").join("archive.json"))?;
+
+    let (stored_count, stored_renumbered) = task_list.merge(storage);
+    let (archived_count, archived_renumbered) = archive.merge(archived);
+
+    let mut output = format!(
+        " {} Imported {} item(s) into the task list and {} into the archive from {}",
+        check_mark(),
+        stored_count,
+        archived_count,
+        data.display().to_string().dimmed()
+    );
+
+    for (list, renumbered) in [
+        ("task list", stored_renumbered),
+        ("archive", archived_renumbered),
+    ] {
+        if !renumbered.is_empty() {
+            let changes: Vec<String> = renumbered
+                .iter()
+                .map(|(old, new)| format!("{} → {}", old, new))
+                .collect();
+            output = format!(
+                "{}\n   Renumbered in the {}: {}",
+                output,
+                list,
+                changes.join(", ").dimmed()
+            );
+        }
+    }
+
+    Ok(output)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::env;
+
+    use crate::tasks::{item_json, list_json};
+    use serde_json::json;
+
+    #[test]
+    fn test_importing_twice_adds_nothing_new() {
+        let directory = env::temp_dir().join(format!("tasker-import-{}", std::process::id()));
+        let storage = directory.join(".taskbook").join("storage");
+        fs::create_dir_all(&storage).unwrap();
+        let item = |id: u64, description: &str| {
+            let fields =
+                json!({"_date": "Mon Mar 14 2022", "_timestamp": id, "description": description});
+            item_json(id, fields)
+        };
+        let items = list_json([item(0, "Taken"), item(5, "Free")]);
+        fs::write(storage.join("storage.json"), items).unwrap();
+        let mut task_list = TaskList::new(String::new()).unwrap();
+        task_list.new_entry(vec!["Ours"], false).unwrap();
+        let mut archive = TaskList::new(String::new()).unwrap();
+
+        let first = import_taskbook(&directory, &mut task_list, &mut archive).unwrap();
+        let second = import_taskbook(&directory, &mut task_list, &mut archive).unwrap();
+
+        assert!(first.contains("Imported 2 item(s) into the task list and 0 into the archive"));
+        assert!(first.contains("Renumbered in the task list: 0 → 1"));
+        assert!(second.contains("Imported 0 item(s)"));
+        assert!(!second.contains("Renumbered"));
+        let records: serde_json::Map<String, serde_json::Value> =
+            serde_json::from_str(&task_list.to_string()).unwrap();
+        let ids: Vec<&String> = records.keys().collect();
+        assert_eq!(ids, ["0", "1", "5"]);
+        fs::remove_dir_all(directory).unwrap();
+    }
+}
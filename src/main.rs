@@ -1,32 +1,160 @@
-use std::{fs, process};
+use std::{env, path, process};
 
+use clipboard::Clipboard;
 use global_settings::Settings;
 use parse::*;
-use tasks::{TaskFlag, TaskList};
+use tasks::{Journal, TaskFlag, TaskList, TaskerError};
 
+mod clipboard;
+mod doctor;
 mod global_settings;
+mod import;
 mod parse;
+mod persist;
 mod tasks;
 
 use clap::{arg, App, ArgMatches};
+use colored::*;
 
 
 fn main() {
     let args: ArgMatches = parse_args();
 
-    parse::create_config_dir();
-    let settings: Settings = load_settings_file(get_settings_location());
-    let mut task_list: TaskList = load_task_list_file(get_task_list_location());
-    let mut archive: TaskList = load_task_list_file(get_archive_location());
+    match run(args) {
+        Ok(output) => println!("{}", output),
+        Err(error) => {
+            eprintln!("{}", error.render());
+            if let TaskerError::Parse { .. } = error {
+                eprintln!("   Run `ts doctor` to check and repair Tasker's files");
+            }
+            process::exit(error.exit_code());
+        }
+    }
+}
+
+/// # Loads everything, runs the command, and saves what it changed
+/// Nothing is saved if the command fails, and the output is only given back once everything
+/// it changed has been written.
+fn run(args: ArgMatches) -> Result<String, TaskerError> {
+    parse::create_config_dir()?;
+    // The doctor has to run before anything is loaded, since loading is what it fixes, so it
+    // takes the lock itself
+    if args.subcommand_matches("doctor").is_some() {
+        return doctor::run_doctor();
+    }
+
+    let settings: Settings = load_settings_file(get_settings_location())?;
+    parse::create_data_dir(&settings)?;
+    let _lock = parse::lock_data_dir(&settings)?;
+    let mut task_list: TaskList = load_task_list_file(get_task_list_location(&settings))?;
+    let mut archive: TaskList = load_task_list_file(get_archive_location(&settings))?;
+    let task_list_repaired = check_task_list(&get_task_list_location(&settings), &mut task_list)?;
+    let archive_repaired = check_task_list(&get_archive_location(&settings), &mut archive)?;
+    // Lists from before uuids existed get them now, and are saved with them
+    let task_list_upgraded = task_list.assign_uuids();
+    let archive_upgraded = archive.assign_uuids();
+
+    let mut journal: Journal = load_journal_file(get_journal_location(&settings))?;
+
+    // Undoing and redoing go through the journal, everything else is recorded in it
+    let outcome = if args.is_present("UNDO") {
+        Outcome::both(journal.undo(&mut task_list, &mut archive))
+    } else if args.is_present("REDO") {
+        Outcome::both(journal.redo(&mut task_list, &mut archive))
+    } else {
+        let before = (task_list.clone(), archive.clone());
+        let outcome = run_program(&settings, &mut task_list, &mut archive, args)?;
+        let operation: Vec<String> = env::args().skip(1).collect();
+        journal.record(operation.join(" "), (&before.0, &before.1), (&task_list, &archive));
+        outcome
+    };
+
+    save_file(get_settings_location(), settings.to_string())?;
+    if outcome.task_list_changed || task_list_repaired || task_list_upgraded {
+        save_file(get_task_list_location(&settings), task_list.to_string())?;
+    }
+    if outcome.archive_changed || archive_repaired || archive_upgraded {
+        save_file(get_archive_location(&settings), archive.to_string())?;
+    }
+    // The journal goes last, so it never records a change that didn't make it to the lists
+    if outcome.task_list_changed || outcome.archive_changed {
+        save_file(get_journal_location(&settings), journal.to_string())?;
+    }
+    Ok(outcome.output)
+}
+
+/// # What running a command did
+/// Besides the output to show, commands say which of the lists they changed, so that the
+/// files of the others are left alone.
+struct Outcome {
+    output: String,
+    task_list_changed: bool,
+    archive_changed: bool,
+}
+
+impl Outcome {
+    fn view(output: String) -> Outcome {
+        Outcome {
+            output,
+            task_list_changed: false,
+            archive_changed: false,
+        }
+    }
+
+    fn task_list(output: String) -> Outcome {
+        Outcome {
+            output,
+            task_list_changed: true,
+            archive_changed: false,
+        }
+    }
 
-    let output = run_program(&settings, &mut task_list, &mut archive, args);
-    fs::write(get_settings_location(), settings.to_string()).expect("Failed to write settings!");
-    fs::write(get_task_list_location(), task_list.to_string()).expect("Failed to write task list!");
-    fs::write(get_archive_location(), archive.to_string()).expect("Failed to write archive!");
-    print!("{}\n", output);
+    fn both(output: String) -> Outcome {
+        Outcome {
+            output,
+            task_list_changed: true,
+            archive_changed: true,
+        }
+    }
+}
+
+/// # Expands selectors like `3-7`, `@work` or `done` into the ids they pick out
+/// When that's more items than were given and more than `confirmSelectionsOver`, the user is
+/// asked first, unless `--force` was given. Ids joined by commas each count as given, the
+/// same as when they're given apart, and only a yes goes ahead.
+fn select(
+    list: &TaskList,
+    selectors: Vec<&str>,
+    settings: &Settings,
+    args: &ArgMatches,
+) -> Result<Vec<String>, TaskerError> {
+    let given = selectors.iter().flat_map(|selector| selector.split(',')).count();
+    let ids = list.select(selectors)?;
+
+    if ids.len() > given && ids.len() > settings.confirm_limit() && !args.is_present("FORCE") {
+        let question = format!("That picks out {} items, go ahead?", ids.len());
+        if !confirm(&question, false)? {
+            return Err(TaskerError::Cancelled);
+        }
+    }
+    Ok(ids)
+}
+
+fn as_strs(ids: &[String]) -> Vec<&str> {
+    ids.iter().map(String::as_str).collect()
+}
+
+/// # Writes one of our files to disk
+/// Files that already hold these contents aren't touched.
+fn save_file(location: path::PathBuf, contents: String) -> Result<(), TaskerError> {
+    match persist::write_if_changed(&location, &contents) {
+        Ok(_) => Ok(()),
+        Err(error) => Err(TaskerError::io(location, error)),
+    }
 }
 
 
+/// # Parses the arguments for the program
 fn parse_args() -> ArgMatches {
     App::new("Tasker")
         .version("0.1")
@@ -35,166 +163,257 @@ fn parse_args() -> ArgMatches {
         .arg(arg!(ARCHIVE: -a --archive "Display archived items"))
         .arg(arg!(BEGIN: -b --begin "Start/pause task")
             .takes_value(true)
+            .multiple_values(true)
+            .multiple_occurrences(true))
+        .arg(arg!(BLOCK: --block "Mark a task as blocked by others")
+            .takes_value(true)
+            .multiple_values(true)
             .multiple_occurrences(true))
         .arg(arg!(CHECK: -c --check "Check/uncheck task")
-            .takes_value(true))
+            .takes_value(true)
+            .multiple_values(true)
+            .multiple_occurrences(true))
         .arg(arg!(CLEAR: --clear "Delete all checked items"))
         .arg(arg!(COPY: -y --copy "Copy item description")
-            .takes_value(true))
+            .takes_value(true)
+            .multiple_values(true)
+            .multiple_occurrences(true))
         .arg(arg!(DELETE: -d --delete "Delete item")
-            .takes_value(true))
+            .takes_value(true)
+            .multiple_values(true)
+            .multiple_occurrences(true))
+        .arg(arg!(DUE: --due "Set or clear when an item is due")
+            .takes_value(true)
+            .multiple_values(true)
+            .multiple_occurrences(true))
         .arg(arg!(EDIT: -e --edit "Edit item description")
-            .takes_value(true))
+            .takes_value(true)
+            .multiple_values(true)
+            .multiple_occurrences(true))
+        .arg(arg!(ESTIMATE: --estimate "Set or clear how long a task should take")
+            .takes_value(true)
+            .multiple_values(true)
+            .multiple_occurrences(true))
         .arg(arg!(FIND: -f --find "Search for items")
-            .takes_value(true))
+            .takes_value(true)
+            .multiple_values(true)
+            .multiple_occurrences(true))
+        .arg(arg!(FORCE: --force "Go ahead without asking, even with blocked tasks"))
         .arg(arg!(LIST: -l --list "List items by attributes")
-            .takes_value(true))
+            .takes_value(true)
+            .multiple_values(true)
+            .multiple_occurrences(true))
         .arg(arg!(MOVE: -m --move "Move item between boards")
-            .takes_value(true))
+            .takes_value(true)
+            .multiple_values(true)
+            .multiple_occurrences(true))
         .arg(arg!(NOTE: -n --note "Create note")
             .takes_value(true)
+            .multiple_values(true)
             .multiple_occurrences(true))
         .arg(arg!(PRIORITY: -p --priority "Update priority of task")
-            .takes_value(true))
+            .takes_value(true)
+            .multiple_values(true)
+            .multiple_occurrences(true))
+        .arg(arg!(REDO: --redo "Redo the last undone command"))
+        .arg(arg!(REPEAT: --repeat "Set or clear how often a task comes back")
+            .takes_value(true)
+            .multiple_values(true)
+            .multiple_occurrences(true))
         .arg(arg!(RESTORE: -r --restore "Restore items from archive")
-            .takes_value(true))
+            .takes_value(true)
+            .multiple_values(true)
+            .multiple_occurrences(true))
         .arg(arg!(STAR: -s --star "Star/unstar item")
-            .takes_value(true))
+            .takes_value(true)
+            .multiple_values(true)
+            .multiple_occurrences(true))
+        .arg(arg!(SUBTASK: --subtask "Add a subtask to a task")
+            .takes_value(true)
+            .multiple_values(true)
+            .multiple_occurrences(true))
         .arg(arg!(TASK: -t --task "Create task")
-            .takes_value(true))
+            .takes_value(true)
+            .multiple_values(true)
+            .multiple_occurrences(true))
         .arg(arg!(TIMELINE: -i --timeline "Display timeline view"))
+        .arg(arg!(UNBLOCK: --unblock "Remove blockers from a task, or all of them")
+            .takes_value(true)
+            .multiple_values(true)
+            .multiple_occurrences(true))
+        .arg(arg!(UNDO: -u --undo "Undo the last command that changed items"))
+        .arg(arg!(UUID: --uuid "Show the uuid that stays with an item for good")
+            .takes_value(true)
+            .multiple_values(true)
+            .multiple_occurrences(true))
+        .subcommand(App::new("doctor")
+            .about("Check Tasker's files and repair any damage"))
+        .subcommand(App::new("report")
+            .about("Sum up what's been done")
+            .arg(arg!(<KIND> "What to report on")
+                .possible_values(["time", "estimates"]))
+            .arg(arg!(--since <WHEN> "Only count from this day on, like monday or 2022-03-01")
+                .required(false)))
+        .subcommand(App::new("import")
+            .about("Merge items from another program into Tasker")
+            .arg(arg!(<SOURCE> "Program to import from")
+                .possible_values(["taskbook"]))
+            .arg(arg!([DIRECTORY] "Directory holding .taskbook.json or the .taskbook directory, defaults to home")))
         .after_help("EXAMPLES:
     $ ts
     $ ts --archive
     $ ts --begin 2 3
+    $ ts --block @7 3 5
     $ ts --check 1 2
+    $ ts --check 3-7
+    $ ts --check 4.2
+    $ ts --check 7 --force
     $ ts --clear
     $ ts --copy 1 2 3
+    $ ts --copy 1,4,9
     $ ts --delete 4
+    $ ts --delete 4.3
+    $ ts --delete @work
+    $ ts --due @3 next fri
     $ ts --edit @3 Merge PR #42
+    $ ts --estimate @3 1h30m
     $ ts --find documentation
     $ ts --list pending coding
     $ ts --move @1 cooking
+    $ ts --move @@work home
     $ ts --note @coding Actually learn rust
     $ ts --priority @3 2
+    $ ts --priority @3-5 3
+    $ ts --repeat @5 mon,thu
     $ ts --restore 4
     $ ts --star 2
+    $ ts --star done
+    $ ts --subtask @4 Write the changelog
     $ ts --task @coding @issues Patch issue 32
     $ ts --task @coding Finish something for once
+    $ ts --task @work Ship release due:friday
+    $ ts --task @work Write the docs est:2h
+    $ ts --task @home Take out the bins repeat:weekly
     $ ts --task Make some buttercream
-    $ ts --timeline")
+    $ ts --timeline
+    $ ts --unblock @7 3
+    $ ts --undo
+    $ ts --uuid 3
+    $ ts --redo
+    $ ts report time --since monday
+    $ ts report estimates
+    $ ts import taskbook ~
+    $ ts doctor")
         .get_matches()
 }
 
-/// # Parses the arguments for the program
-// fn parse_args() -> ArgMatches {
-//     clap_app!(tasker =>
-//         (version: "0.1")
-//         (about: "a rust clone of Taskbook")
-//         (override_usage: "$ ts [<options> ...]")
-//         (@arg ARCHIVE: -a --archive "Display archived items")
-//         (@arg BEGIN: -b --begin +takes_value +multiple "Start/pause task")
-//         (@arg CHECK: -c --check +takes_value "Check/uncheck task")
-//         (@arg CLEAR: --clear "Delete all checked items")
-//         (@arg COPY: -y --copy +takes_value "Copy item description")
-//         (@arg DELETE: -d --delete +takes_value "Delete item")
-//         (@arg EDIT: -e --edit +takes_value "Edit item description")
-//         (@arg FIND: -f --find +takes_value "Search for items")
-//         (@arg LIST: -l --list +takes_value "List items by attributes")
-//         (@arg MOVE: -m --move +takes_value "Move item between boards")
-//         (@arg NOTE: -n --note +takes_value +multiple "Create note")
-//         (@arg PRIORITY: -p --priority +takes_value "Update priority of task")
-//         (@arg RESTORE: -r --restore +takes_value "Restore items from archive")
-//         (@arg STAR: -s --start +takes_value "Star/unstar item")
-//         (@arg TASK: -t --task +takes_value "Create task")
-//         (@arg TIMELINE: -i --timeline "Display timeline view")
-//         (after_help: "EXAMPLES:
-    // $ ts
-    // $ ts --archive
-    // $ ts --begin 2 3
-    // $ ts --check 1 2
-    // $ ts --clear
-    // $ ts --copy 1 2 3
-    // $ ts --delete 4
-    // $ ts --edit @3 Merge PR #42
-    // $ ts --find documentation
-    // $ ts --list pending coding
-    // $ ts --move @1 cooking
-    // $ ts --note @coding Actually learn rust
-    // $ ts --priority @3 2
-    // $ ts --restore 4
-    // $ ts --star 2
-    // $ ts --task @coding @issues Patch issue 32
-    // $ ts --task @coding Finish something for once
-    // $ ts --task Make some buttercream
-    // $ ts --timeline")
-    // )
-//     .get_matches()
-// }
-
 fn run_program(
     settings: &Settings,
     task_list: &mut TaskList,
     archive: &mut TaskList,
     args: ArgMatches,
-) -> String {
+) -> Result<Outcome, TaskerError> {
+    // Subcommands
+    // IMPORT
+    if let Some(import) = args.subcommand_matches("import") {
+        let directory = match import.value_of("DIRECTORY") {
+            Some(directory) => path::PathBuf::from(directory),
+            None => dirs::home_dir().unwrap_or_default(),
+        };
+        let output = import::import_taskbook(&directory, task_list, archive)?;
+        return Ok(Outcome::both(output));
+    }
+
+    // REPORT
+    if let Some(report) = args.subcommand_matches("report") {
+        let since = report.value_of("since");
+        let output = match report.value_of("KIND") {
+            Some("estimates") => archive.estimate_report(since)?,
+            _ => task_list.time_report(archive, since)?,
+        };
+        return Ok(Outcome::view(output));
+    }
+
     // Error handling
-    // BEGIN, CHECK, DELETE, EDIT, MOVE, PRIORITY, RESTORE, STAR
-    if let Some(begin) = args.values_of("BEGIN") {
-        process_error(task_list.flip_task_flag(begin.collect(), TaskFlag::Begin))
+    // BEGIN, BLOCK, CHECK, DELETE, DUE, EDIT, ESTIMATE, MOVE, PRIORITY, REPEAT, RESTORE, STAR,
+    // UNBLOCK
+    let outcome = if let Some(begin) = args.values_of("BEGIN") {
+        let ids = select(task_list, begin.collect(), settings, &args)?;
+        if !args.is_present("FORCE") {
+            task_list.ensure_unblocked(&as_strs(&ids), TaskFlag::Begin)?;
+        }
+        Outcome::task_list(task_list.flip_task_flag(as_strs(&ids), TaskFlag::Begin, archive, settings)?)
+    } else if let Some(block) = args.values_of("BLOCK") {
+        Outcome::task_list(task_list.block(block.collect())?)
     } else if let Some(check) = args.values_of("CHECK") {
-        process_error(task_list.flip_task_flag(check.collect(), TaskFlag::Check))
+        let ids = select(task_list, check.collect(), settings, &args)?;
+        if !args.is_present("FORCE") {
+            task_list.ensure_unblocked(&as_strs(&ids), TaskFlag::Check)?;
+        }
+        Outcome::both(task_list.flip_task_flag(as_strs(&ids), TaskFlag::Check, archive, settings)?)
     } else if let Some(delete) = args.values_of("DELETE") {
-        process_error(task_list.move_tasks_between_lists(archive, Some(delete.collect()), false))
+        let ids = select(task_list, delete.collect(), settings, &args)?;
+        Outcome::both(task_list.move_tasks_between_lists(archive, Some(as_strs(&ids)), false)?)
+    } else if let Some(due) = args.values_of("DUE") {
+        Outcome::task_list(task_list.due(due.collect())?)
     } else if let Some(edit) = args.values_of("EDIT") {
-        process_error(task_list.edit(edit.collect()))
+        Outcome::task_list(task_list.edit(edit.collect())?)
+    } else if let Some(estimate) = args.values_of("ESTIMATE") {
+        Outcome::task_list(task_list.estimate(estimate.collect())?)
     } else if let Some(move_list) = args.values_of("MOVE") {
-        process_error(task_list.move_to_board(move_list.collect()))
+        let (target, boards) = tasks::take_target(move_list.collect())?;
+        let ids = select(task_list, vec![target], settings, &args)?;
+        Outcome::task_list(task_list.move_to_board(as_strs(&ids), boards)?)
     } else if let Some(priority) = args.values_of("PRIORITY") {
-        process_error(task_list.priority(priority.collect()))
+        let (target, words) = tasks::take_target(priority.collect())?;
+        let ids = select(task_list, vec![target], settings, &args)?;
+        Outcome::task_list(task_list.priority(as_strs(&ids), words)?)
+    } else if let Some(repeat) = args.values_of("REPEAT") {
+        Outcome::task_list(task_list.repeat(repeat.collect())?)
     } else if let Some(restore) = args.values_of("RESTORE") {
-        process_error(archive.move_tasks_between_lists(task_list, Some(restore.collect()), true))
+        let ids = select(archive, restore.collect(), settings, &args)?;
+        Outcome::both(archive.move_tasks_between_lists(task_list, Some(as_strs(&ids)), true)?)
     } else if let Some(star) = args.values_of("STAR") {
-        process_error(task_list.flip_task_flag(star.collect(), TaskFlag::Star))
+        let ids = select(task_list, star.collect(), settings, &args)?;
+        Outcome::task_list(task_list.flip_task_flag(as_strs(&ids), TaskFlag::Star, archive, settings)?)
+    } else if let Some(unblock) = args.values_of("UNBLOCK") {
+        Outcome::task_list(task_list.unblock(unblock.collect())?)
     }
     // Regular output
-    // FIND, LIST, NOTE, TASK
-    // else if args.is_present("FIND") {
-
-    // } else if args.is_present("LIST") {
-
-    // }
-    else if let Some(note) = args.values_of("NOTE") {
-        task_list.new_entry(note.collect(), true)
+    // FIND, LIST, NOTE, SUBTASK, TASK
+    else if let Some(find) = args.values_of("FIND") {
+        Outcome::view(task_list.find(find.collect(), settings))
+    } else if let Some(list) = args.values_of("LIST") {
+        Outcome::view(task_list.list_view(list.collect(), settings))
+    } else if let Some(note) = args.values_of("NOTE") {
+        Outcome::task_list(task_list.new_entry(note.collect(), true)?)
+    } else if let Some(subtask) = args.values_of("SUBTASK") {
+        Outcome::task_list(task_list.add_subtask(subtask.collect())?)
     } else if let Some(task) = args.values_of("TASK") {
-        task_list.new_entry(task.collect(), false)
+        Outcome::task_list(task_list.new_entry(task.collect(), false)?)
     }
     // Special output
-    // ARCHIVE, CLEAR, COPY, TIMELINE, none
+    // ARCHIVE, CLEAR, COPY, TIMELINE, UUID, none
     else if args.is_present("ARCHIVE") {
-        archive.board_view()
+        Outcome::view(archive.archive_view(settings))
     } else if args.is_present("CLEAR") {
-        task_list
-            .move_tasks_between_lists(archive, None, false)
-            .expect("Failed to clear the completed tasks!")
-    } else if args.is_present("COPY") {
-        "Clipboard isn't supported yet.".into()
+        Outcome::both(task_list.move_tasks_between_lists(archive, None, false)?)
+    } else if let Some(copy) = args.values_of("COPY") {
+        let ids = select(task_list, copy.collect(), settings, &args)?;
+        let descriptions = task_list.get_descriptions(as_strs(&ids))?;
+        Clipboard::from_env().copy(&descriptions)?;
+        Outcome::view(format!(
+            " {} Copied the description of item(s): {}",
+            tasks::check_mark(),
+            ids.join(", ").dimmed()
+        ))
     } else if args.is_present("TIMELINE") {
-        task_list.board_view()
+        Outcome::view(task_list.timeline_view(settings))
+    } else if let Some(uuid) = args.values_of("UUID") {
+        Outcome::view(task_list.get_uuids(uuid.collect())?)
     } else {
-        match settings.show_completed() {
-            true => task_list.board_view(),
-            false => task_list.board_view(),
-        }
-    }
-}
+        Outcome::view(task_list.board_view(settings))
+    };
 
-fn process_error(result: Result<String, String>) -> String {
-    match result {
-        Ok(value) => value,
-        Err(err) => {
-            print!("{}", err);
-            process::exit(1);
-        }
-    }
+    Ok(outcome)
 }
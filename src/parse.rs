@@ -1,31 +1,19 @@
+use super::persist;
 use super::Settings;
-use super::TaskList;
+use super::{Journal, TaskList, TaskerError};
 
-use std::{fs, io, path, process};
+use std::{env, fs, io, path, time};
 
-use dirs;
+use colored::*;
 
 /// # Creates the config directory
 /// This doesn't attempt to do any _real_ error handling. If the directory doesn't exist it
-/// makes the directory, if it does it doesn't do anything. If any other error is raised though
-/// it'll panic and crash since it's something we probably can't recover from.
-pub fn create_config_dir() {
+/// makes the directory and any missing parents, if it does it doesn't do anything. Any other
+/// error is passed back, since it's something we probably can't recover from.
+pub fn create_config_dir() -> Result<(), TaskerError> {
     let directory: path::PathBuf = get_base_location();
 
-    match fs::create_dir(&directory) {
-        Ok(_) => return,
-        Err(error) => match error.kind() {
-            io::ErrorKind::AlreadyExists => return,
-            _ => {
-                eprintln!(
-                    "Couldn't create base directory at {}, unknown error: {}",
-                    directory.to_str().unwrap(),
-                    error
-                );
-                process::exit(1);
-            }
-        },
-    }
+    fs::create_dir_all(&directory).map_err(|error| TaskerError::io(directory, error))
 }
 
 /// # Searches for the base directory for the program
@@ -55,28 +43,22 @@ pub fn get_settings_location() -> path::PathBuf {
 }
 
 /// # Creates the Settings struct from the settings file
-/// This will load in the settings from the file, handle any errors, and crash if it can't
-/// be fixed.
-pub fn load_settings_file(path_to_settings: path::PathBuf) -> Settings {
+/// This will load in the settings from the file, handle any errors, and give back an error if
+/// it can't be fixed.
+pub fn load_settings_file(path_to_settings: path::PathBuf) -> Result<Settings, TaskerError> {
     // Parse the settings file
     let settings_contents = match fs::read_to_string(&path_to_settings) {
         Ok(file) => file,
         Err(error) => match error.kind() {
             io::ErrorKind::NotFound => String::new(),
-            _ => {
-                eprintln!(
-                    "Failed to open the settings file at {}!",
-                    path_to_settings.to_str().unwrap()
-                );
-                process::exit(1);
-            }
+            _ => return Err(TaskerError::io(path_to_settings, error)),
         },
     };
 
     // Load the global settings
     match Settings::new(settings_contents) {
-        Ok(parsed_settings) => parsed_settings,
-        Err(_) => handle_broken_settings(path_to_settings),
+        Ok(parsed_settings) => Ok(parsed_settings),
+        Err(error) => handle_broken_settings(path_to_settings, error),
     }
 }
 
@@ -86,72 +68,252 @@ pub fn load_settings_file(path_to_settings: path::PathBuf) -> Settings {
 /// anything by replacing the file.
 ///
 /// So what this does is it asks the user if they would like for the settings to go ahead and
-/// be cleared. If they do, we replace it, if not we'll go ahead and just crash as normal.
-pub fn handle_broken_settings(path_to_settings: path::PathBuf) -> Settings {
-    println!("Settings file is damaged, replace with defaults? [Y/n]");
+/// be cleared. If they do, we replace it, if not we'll go ahead and give back the error.
+pub fn handle_broken_settings(
+    path_to_settings: path::PathBuf,
+    error: serde_json::Error,
+) -> Result<Settings, TaskerError> {
+    if confirm("Settings file is damaged, replace with defaults?", true)? {
+        return Ok(Settings::new("".into()).unwrap());
+    }
+
+    Err(TaskerError::parse(path_to_settings, error))
+}
+
+/// # Asks the user a yes or no question
+/// An empty answer counts as `default`, which the prompt shows in capitals. When there's
+/// nobody to answer, because stdin is closed, the answer is no so that scripts never end up
+/// changing anything by accident.
+pub fn confirm(question: &str, default: bool) -> Result<bool, TaskerError> {
+    let choices = if default { "[Y/n]" } else { "[y/N]" };
+    eprint!("{} {} ", question, choices);
 
     let mut user_input = String::new();
-    io::stdin()
+    let read = io::stdin()
         .read_line(&mut user_input)
-        .expect("Failed to read user's input!");
+        .map_err(|error| TaskerError::io("stdin", error))?;
 
-    let overwrite = match user_input.to_lowercase().chars().nth(0).unwrap() {
-        'y' => true,
-        _ => false,
-    };
+    if read == 0 {
+        eprintln!();
+        return Ok(false);
+    }
+
+    match user_input.trim().to_lowercase().chars().next() {
+        None => Ok(default),
+        Some(answer) => Ok(answer == 'y'),
+    }
+}
+
+/// # Checks a freshly loaded task list
+/// Every problem found is listed with the id of the item it's in, and the user is asked if
+/// they should be repaired. Gives back whether the list was changed, so it can be saved.
+pub fn check_task_list(
+    path_to_task_list: &path::Path,
+    task_list: &mut TaskList,
+) -> Result<bool, TaskerError> {
+    let issues = task_list.validate();
+    if issues.is_empty() {
+        return Ok(false);
+    }
 
-    if overwrite {
+    eprintln!(
+        " {} {} has {} problem(s):",
+        "!".yellow(),
+        path_to_task_list.display(),
+        issues.len()
+    );
+    for issue in &issues {
         eprintln!(
-            "{} is damaged! Please fix before calling tasker again",
-            path_to_settings.to_str().unwrap()
+            "    {} {}",
+            format!("{}.", issue.id).dimmed(),
+            issue.problem
         );
-        process::exit(1);
     }
 
-    Settings::new("".into()).unwrap()
+    if confirm("Repair them?", true)? {
+        task_list.repair();
+        Ok(true)
+    } else {
+        Ok(false)
+    }
+}
+
+/// # Searches for the directory the task lists are kept in
+/// This is the `taskbookDirectory` from the settings, with a leading `~` swapped for the home
+/// directory, or the config directory when it isn't set, which is where the lists have always
+/// been. Setting `TASKER_DIR` in the environment overrides it, which is handy for scripts.
+pub fn get_data_location(settings: &Settings) -> path::PathBuf {
+    match (env::var_os("TASKER_DIR"), settings.directory()) {
+        (Some(directory), _) if !directory.is_empty() => path::PathBuf::from(directory),
+        (_, Some(directory)) => expand_home(directory),
+        (_, None) => get_base_location(),
+    }
+}
+
+/// # Swaps a leading `~` for the home directory
+/// Only `~` on its own or followed by a separator is expanded, `~user` is left alone.
+pub fn expand_home(location: &str) -> path::PathBuf {
+    let rest = match location.strip_prefix('~') {
+        Some(rest) if rest.is_empty() || rest.starts_with(['/', '\\']) => rest,
+        _ => return path::PathBuf::from(location),
+    };
+
+    match dirs::home_dir() {
+        Some(mut home) => {
+            home.push(rest.trim_start_matches(['/', '\\']));
+            home
+        }
+        None => path::PathBuf::from(location),
+    }
+}
+
+/// # Creates the data directory
+/// Unlike the config directory, this one can be anywhere, so every missing parent is made too.
+/// Failing to do so means we have nowhere to keep the tasks.
+pub fn create_data_dir(settings: &Settings) -> Result<(), TaskerError> {
+    let directory: path::PathBuf = get_data_location(settings);
+
+    fs::create_dir_all(&directory).map_err(|error| TaskerError::io(directory, error))
+}
+
+/// # Locks the data directory
+/// This keeps two `ts` processes from loading the lists at the same time and then overwriting
+/// each other's changes. If the lock can't be had within a few seconds we give up, rather
+/// than risk losing anything.
+pub fn lock_data_dir(settings: &Settings) -> Result<persist::DirectoryLock, TaskerError> {
+    lock_within(&get_data_location(settings), time::Duration::from_secs(10))
+}
+
+fn lock_within(
+    directory: &path::Path,
+    timeout: time::Duration,
+) -> Result<persist::DirectoryLock, TaskerError> {
+    persist::lock_directory(directory, timeout).map_err(|error| TaskerError::io(directory, error))
 }
 
 /// # Get the path to the task list
-/// This will return the path to where they task list should be stored. This will should work
-/// on all platforms, and requires create_config_dir to be called first
-pub fn get_task_list_location() -> path::PathBuf {
-    let mut config: path::PathBuf = get_base_location();
+/// This will return the path to where the task list should be stored, inside the data
+/// directory. This requires create_data_dir to be called first
+pub fn get_task_list_location(settings: &Settings) -> path::PathBuf {
+    let mut config: path::PathBuf = get_data_location(settings);
     config.push("storage");
     config.set_extension("json");
     config
 }
 
-/// # Get the path to the task list
-/// This will return the path to where they task list should be stored. This will should work
-/// on all platforms, and requires create_config_dir to be called first
-pub fn get_archive_location() -> path::PathBuf {
-    let mut config: path::PathBuf = get_base_location();
+/// # Get the path to the archive
+/// This will return the path to where the archive should be stored, inside the data
+/// directory. This requires create_data_dir to be called first
+pub fn get_archive_location(settings: &Settings) -> path::PathBuf {
+    let mut config: path::PathBuf = get_data_location(settings);
     config.push("archive");
     config.set_extension("json");
     config
 }
 
-/// # Creates the task list from
-/// Loads in the data from the
-pub fn load_task_list_file(path_to_task_list: path::PathBuf) -> TaskList {
+/// # Creates the task list from a file
+/// Loads in the data from the file at the given path. A missing file is just an empty list.
+pub fn load_task_list_file(path_to_task_list: path::PathBuf) -> Result<TaskList, TaskerError> {
     // Parse the task storage file
-    let task_contents = match fs::read_to_string(path_to_task_list) {
+    let task_contents = match fs::read_to_string(&path_to_task_list) {
         Ok(file) => file,
         Err(error) => match error.kind() {
             io::ErrorKind::NotFound => String::new(),
-            _ => {
-                eprintln!("Failed to open the tasklist! Error {:?}", error);
-                process::exit(1);
-            }
+            _ => return Err(TaskerError::io(path_to_task_list, error)),
         },
     };
 
     // Load the task list
-    match TaskList::new(task_contents) {
-        Ok(tasks) => tasks,
-        Err(error) => {
-            eprintln!("Failed to parse the tasklist! Error {:?}", error);
-            process::exit(1);
+    TaskList::new(task_contents).map_err(|error| TaskerError::parse(path_to_task_list, error))
+}
+
+/// # Get the path to the journal
+/// The journal lives next to the lists it records changes to, in the data directory.
+pub fn get_journal_location(settings: &Settings) -> path::PathBuf {
+    let mut config: path::PathBuf = get_data_location(settings);
+    config.push("journal");
+    config.set_extension("json");
+    config
+}
+
+/// # Loads the journal of past commands
+/// A missing file just means nothing has been done yet.
+pub fn load_journal_file(path_to_journal: path::PathBuf) -> Result<Journal, TaskerError> {
+    let journal_contents = match fs::read_to_string(&path_to_journal) {
+        Ok(file) => file,
+        Err(error) => match error.kind() {
+            io::ErrorKind::NotFound => String::new(),
+            _ => return Err(TaskerError::io(path_to_journal, error)),
+        },
+    };
+
+    Journal::new(journal_contents).map_err(|error| TaskerError::parse(path_to_journal, error))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_expand_home() {
+        let home = dirs::home_dir().unwrap();
+
+        assert_eq!(expand_home("~"), home);
+        assert_eq!(expand_home("~/x"), home.join("x"));
+        assert_eq!(expand_home("~user/x"), path::PathBuf::from("~user/x"));
+        assert_eq!(expand_home("/srv/~"), path::PathBuf::from("/srv/~"));
+    }
+
+    #[test]
+    fn test_second_lock_times_out() {
+        let directory = env::temp_dir().join(format!("tasker-lock-{}", std::process::id()));
+        fs::create_dir_all(&directory).unwrap();
+        let timeout = time::Duration::from_millis(100);
+
+        let lock = lock_within(&directory, timeout).unwrap();
+        match lock_within(&directory, timeout) {
+            Err(TaskerError::Io { source, .. }) => {
+                assert_eq!(source.kind(), io::ErrorKind::TimedOut)
+            }
+            _ => panic!("the directory was locked twice"),
         }
+
+        drop(lock);
+        assert!(lock_within(&directory, timeout).is_ok());
+        fs::remove_dir_all(directory).unwrap();
+    }
+
+    /// Everything touching `TASKER_DIR` is in here, since tests share the environment.
+    #[test]
+    fn test_data_location() {
+        let settings = |directory: &str| {
+            let json = format!(
+                r#"{{"taskbookDirectory": "{}", "displayCompleteTasks": true,
+                "displayProgressOverview": true}}"#,
+                directory
+            );
+            Settings::new(json).unwrap()
+        };
+        let default = Settings::new(String::new()).unwrap();
+        env::remove_var("TASKER_DIR");
+
+        assert_eq!(get_data_location(&default), get_base_location());
+        assert_eq!(
+            get_data_location(&settings("~/.config/tasker")),
+            get_base_location()
+        );
+        assert_eq!(
+            get_data_location(&settings("~/tasks")),
+            dirs::home_dir().unwrap().join("tasks")
+        );
+
+        env::set_var("TASKER_DIR", "/srv/tasks");
+        assert_eq!(
+            get_data_location(&settings("~/tasks")),
+            path::PathBuf::from("/srv/tasks")
+        );
+        env::set_var("TASKER_DIR", "");
+        assert_eq!(get_data_location(&default), get_base_location());
+        env::remove_var("TASKER_DIR");
     }
 }
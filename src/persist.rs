@@ -0,0 +1,141 @@
+use std::io::Write;
+use std::time::{Duration, Instant};
+use std::{ffi, fs, io, path, thread};
+
+/// # An exclusive hold on a data directory
+/// While this is alive, no other `ts` can lock the same directory. The lock is released when
+/// it's dropped, or by the operating system when the process exits.
+pub struct DirectoryLock {
+    _file: fs::File,
+}
+
+/// # Locks a data directory for the rest of a command
+/// This waits for other `ts` processes to finish with the directory, giving up once `timeout`
+/// has passed. The lock is advisory and kept on a `.lock` file inside the directory, so it
+/// only keeps out other copies of Tasker.
+pub fn lock_directory(directory: &path::Path, timeout: Duration) -> io::Result<DirectoryLock> {
+    let file = fs::OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(directory.join(".lock"))?;
+    let started = Instant::now();
+
+    loop {
+        match file.try_lock() {
+            Ok(()) => return Ok(DirectoryLock { _file: file }),
+            Err(fs::TryLockError::WouldBlock) if started.elapsed() < timeout => {
+                thread::sleep(Duration::from_millis(50));
+            }
+            Err(fs::TryLockError::WouldBlock) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::TimedOut,
+                    format!(
+                        "another ts has been using it for over {} seconds",
+                        timeout.as_secs()
+                    ),
+                ));
+            }
+            Err(fs::TryLockError::Error(error)) => return Err(error),
+        }
+    }
+}
+
+/// # Writes a file without ever leaving it half written
+/// The old contents are first kept in a `.bak` file beside it, so there's something to go back
+/// to if the new contents turn out to be wrong, and then the file is [replaced](replace).
+pub fn write_atomic(location: &path::Path, contents: &str) -> io::Result<()> {
+    if location.exists() {
+        let backup = backup_location(location);
+        fs::copy(location, &backup)?;
+        fs::File::open(&backup)?.sync_all()?;
+    }
+
+    replace(location, contents)
+}
+
+/// # Replaces a file without touching its backup
+/// The contents go to a temporary file next to the real one, which is synced to disk and then
+/// renamed over it. Renaming within a directory is atomic, so after a crash the file either
+/// holds the old contents or the new ones.
+pub fn replace(location: &path::Path, contents: &str) -> io::Result<()> {
+    let directory = match location.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => path::Path::new("."),
+    };
+    let temporary = sibling(location, ".", ".tmp");
+
+    if let Err(error) = write_synced(&temporary, contents) {
+        let _ = fs::remove_file(&temporary);
+        return Err(error);
+    }
+
+    fs::rename(&temporary, location)?;
+    sync_directory(directory)
+}
+
+/// # Writes a file only if its contents would change
+/// This avoids touching files that are synced elsewhere when nothing about them changed, and
+/// gives back whether anything was written.
+pub fn write_if_changed(location: &path::Path, contents: &str) -> io::Result<bool> {
+    match fs::read_to_string(location) {
+        Ok(current) if current == contents => Ok(false),
+        _ => write_atomic(location, contents).map(|_| true),
+    }
+}
+
+/// # Get the path to the backup of a file
+/// This is where [`write_atomic`] keeps the previous contents of the file.
+pub fn backup_location(location: &path::Path) -> path::PathBuf {
+    sibling(location, "", ".bak")
+}
+
+/// Builds a path in the same directory, with the file name wrapped in a prefix and suffix.
+pub fn sibling(location: &path::Path, prefix: &str, suffix: &str) -> path::PathBuf {
+    let mut name = ffi::OsString::from(prefix);
+    name.push(location.file_name().unwrap_or_default());
+    name.push(suffix);
+    location.with_file_name(name)
+}
+
+fn write_synced(location: &path::Path, contents: &str) -> io::Result<()> {
+    let mut file = fs::File::create(location)?;
+    file.write_all(contents.as_bytes())?;
+    file.sync_all()
+}
+
+/// The rename itself only survives a crash once the directory entry is on disk. Directories
+/// can't be opened like this on Windows, where the rename is already durable.
+#[cfg(unix)]
+fn sync_directory(directory: &path::Path) -> io::Result<()> {
+    fs::File::open(directory)?.sync_all()
+}
+
+#[cfg(not(unix))]
+fn sync_directory(_directory: &path::Path) -> io::Result<()> {
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::env;
+
+    #[test]
+    fn test_keeps_previous_version() {
+        let directory = env::temp_dir().join(format!("tasker-persist-{}", std::process::id()));
+        fs::create_dir_all(&directory).unwrap();
+        let location = directory.join("storage.json");
+
+        write_atomic(&location, "first").unwrap();
+        write_atomic(&location, "second").unwrap();
+
+        assert_eq!(fs::read_to_string(&location).unwrap(), "second");
+        assert_eq!(
+            fs::read_to_string(backup_location(&location)).unwrap(),
+            "first"
+        );
+        assert!(!directory.join(".storage.json.tmp").exists());
+        fs::remove_dir_all(directory).unwrap();
+    }
+}
@@ -0,0 +1,192 @@
+use colored::*;
+
+use super::*;
+
+impl TaskList {
+    /// # Marks a task as waiting on others
+    /// Takes the `@id` of the blocked task followed by the ids blocking it. Links that would
+    /// make a task wait on itself, directly or through others, are refused, and tasks that
+    /// are already checked are skipped since they aren't in the way.
+    pub fn block(&mut self, input: Vec<&str>) -> Result<String, TaskerError> {
+        let (id, words): (u64, Vec<String>) = self.get_task_id_from_input(input)?;
+        if words.is_empty() {
+            return Err(TaskerError::MissingId);
+        }
+        if !self.tasks.contains_key(&id) {
+            return Err(TaskerError::UnknownId(id.to_string()));
+        }
+
+        let mut blockers: Vec<u64> = Vec::new();
+        let mut skipped: Vec<u64> = Vec::new();
+        for word in &words {
+            let blocker = self.find_id(word)?;
+            match self.tasks.get(&blocker) {
+                None => return Err(TaskerError::UnknownId(blocker.to_string())),
+                Some(task) if task.is_complete() => skipped.push(blocker),
+                Some(_) => blockers.push(blocker),
+            }
+            if let Some(cycle) = self.path_between(blocker, id) {
+                return Err(TaskerError::DependencyCycle(cycle));
+            }
+        }
+
+        let task = self.tasks.get_mut(&id).unwrap();
+        for blocker in blockers {
+            task.add_blocker(blocker);
+        }
+
+        let mut output = format!(
+            " {} Item {} is blocked by: {}",
+            check_mark(),
+            id.to_string().dimmed(),
+            describe_blockers(task.get_blockers())
+        );
+        if !skipped.is_empty() {
+            output = format!(
+                "{}\n {} Skipped checked item(s): {}",
+                output,
+                "!".yellow(),
+                describe_blockers(&skipped)
+            );
+        }
+        Ok(output)
+    }
+
+    /// # Stops a task waiting on others
+    /// Takes the `@id` of the blocked task, followed by the blockers to remove, or none to
+    /// remove them all.
+    pub fn unblock(&mut self, input: Vec<&str>) -> Result<String, TaskerError> {
+        let (id, words): (u64, Vec<String>) = self.get_task_id_from_input(input)?;
+        let blockers: Vec<u64> = words
+            .iter()
+            .map(|word| self.find_id(word))
+            .collect::<Result<_, _>>()?;
+
+        let task = match self.tasks.get_mut(&id) {
+            Some(task) => task,
+            None => return Err(TaskerError::UnknownId(id.to_string())),
+        };
+        if blockers.is_empty() {
+            task.clear_blockers();
+        } else {
+            for blocker in blockers {
+                task.remove_blocker(blocker);
+            }
+        }
+
+        Ok(format!(
+            " {} Item {} is blocked by: {}",
+            check_mark(),
+            id.to_string().dimmed(),
+            describe_blockers(task.get_blockers())
+        ))
+    }
+
+    /// # Refuses to start or check blocked tasks
+    /// Only tasks the flag would turn on are checked, so a blocked task can still be paused
+    /// or unchecked.
+    pub fn ensure_unblocked(&self, ids: &[&str], flag: TaskFlag) -> Result<(), TaskerError> {
+        for id in ids {
+            let task = match self.find_id(id).ok().and_then(|id| self.tasks.get(&id)) {
+                Some(task) => task,
+                // Unknown ids and subtasks are left for the command itself to deal with
+                None => continue,
+            };
+
+            let turning_on = match flag {
+                TaskFlag::Begin => !task.in_progress(),
+                TaskFlag::Check => !task.is_complete(),
+                TaskFlag::Star => false,
+            };
+            if turning_on && !task.get_blockers().is_empty() {
+                return Err(TaskerError::Blocked {
+                    id: task.get_id(),
+                    by: task.get_blockers().to_vec(),
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// # Lets go of everything a task was blocking
+    /// Done when the task is checked or leaves the list, since it's no longer in the way.
+    pub(super) fn release(&mut self, id: u64) {
+        for task in self.tasks.values_mut() {
+            task.remove_blocker(id);
+        }
+    }
+
+    /// # Finds a chain of blockers leading from one task to another
+    /// Gives back the ids along the way, starting with `to` and ending with `to` again, as
+    /// that's the loop linking them would make.
+    fn path_between(&self, from: u64, to: u64) -> Option<Vec<u64>> {
+        if from == to {
+            return Some(vec![to, to]);
+        }
+
+        let mut stack: Vec<Vec<u64>> = vec![vec![from]];
+        let mut seen: Vec<u64> = vec![from];
+        while let Some(path) = stack.pop() {
+            let last = *path.last().unwrap();
+            let blockers = match self.tasks.get(&last) {
+                Some(task) => task.get_blockers(),
+                None => continue,
+            };
+
+            for blocker in blockers {
+                let mut next = path.clone();
+                next.push(*blocker);
+                if *blocker == to {
+                    next.insert(0, to);
+                    return Some(next);
+                }
+                if !seen.contains(blocker) {
+                    seen.push(*blocker);
+                    stack.push(next);
+                }
+            }
+        }
+        None
+    }
+}
+
+fn describe_blockers(blockers: &[u64]) -> ColoredString {
+    if blockers.is_empty() {
+        "nothing".dimmed()
+    } else {
+        let ids: Vec<String> = blockers.iter().map(|id| id.to_string()).collect();
+        ids.join(", ").dimmed()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::global_settings::Settings;
+
+    #[test]
+    fn test_blocking_and_releasing() {
+        let mut list = TaskList::new(String::new()).unwrap();
+        let mut archive = TaskList::new(String::new()).unwrap();
+        let settings = Settings::new(String::new()).unwrap();
+        for description in ["Design", "Build", "Ship"] {
+            list.new_entry(vec![description], false).unwrap();
+        }
+
+        list.block(vec!["@2", "1"]).unwrap();
+        list.block(vec!["@1", "0"]).unwrap();
+        assert!(matches!(
+            list.block(vec!["@0", "2"]),
+            Err(TaskerError::DependencyCycle(cycle)) if cycle == vec![0, 2, 1, 0]
+        ));
+        assert!(list.ensure_unblocked(&["1"], TaskFlag::Check).is_err());
+
+        list.flip_task_flag(vec!["0"], TaskFlag::Check, &mut archive, &settings)
+            .unwrap();
+        assert!(list.ensure_unblocked(&["1"], TaskFlag::Check).is_ok());
+
+        list.move_tasks_between_lists(&mut archive, Some(vec!["1"]), false)
+            .unwrap();
+        assert!(list.tasks[&2].get_blockers().is_empty());
+    }
+}
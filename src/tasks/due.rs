@@ -0,0 +1,219 @@
+use chrono::{Datelike, Duration, NaiveDate, Weekday};
+
+/// Due dates are stored in the item as ISO dates, so they stay readable in the JSON.
+pub const DUE_FORMAT: &str = "%Y-%m-%d";
+
+/// Items due within this many days are highlighted as due soon.
+const SOON_DAYS: i64 = 3;
+
+/// Dates don't go this far, and counts much bigger can't even be made into a [`Duration`].
+const MAX_OFFSET_DAYS: i64 = 100_000_000;
+
+/// # How close an item is to being due
+#[derive(Debug, PartialEq)]
+pub enum Urgency {
+    /// Past the due date by this many days
+    Overdue(i64),
+    Today,
+    /// Due within the next few days
+    Soon(i64),
+    Later,
+}
+
+impl Urgency {
+    pub fn of(due: NaiveDate, today: NaiveDate) -> Urgency {
+        let days = (due - today).num_days();
+
+        if days < 0 {
+            Urgency::Overdue(-days)
+        } else if days == 0 {
+            Urgency::Today
+        } else if days <= SOON_DAYS {
+            Urgency::Soon(days)
+        } else {
+            Urgency::Later
+        }
+    }
+}
+
+/// # Works out which day a due date phrase means
+/// Takes ISO dates (`2022-03-14`), `today`, `tomorrow`, weekdays (`fri`, `next mon`),
+/// `next week`, `next month`, and offsets like `in 3d`, `2w`, or `in 1 month`. A weekday on
+/// its own is the next one to come, including today, while `next` always skips today. Words
+/// can be joined with `-` or `_` so a phrase fits in a single `due:` token.
+pub fn parse_due(phrase: &str, today: NaiveDate) -> Option<NaiveDate> {
+    let phrase = phrase.trim().to_lowercase();
+    if let Ok(date) = NaiveDate::parse_from_str(&phrase, DUE_FORMAT) {
+        return Some(date);
+    }
+
+    let phrase = phrase.replace(['-', '_'], " ");
+    let words: Vec<&str> = phrase.split_whitespace().collect();
+    match words.as_slice() {
+        ["today"] => Some(today),
+        ["tomorrow"] => Some(today + Duration::days(1)),
+        ["next", "week"] => Some(today + Duration::days(7)),
+        ["next", "month"] => add_months(today, 1),
+        ["next", day] => next_weekday(today, parse_weekday(day)?, 1),
+        [word] => match parse_weekday(word) {
+            Some(weekday) => next_weekday(today, weekday, 0),
+            None => add_offset(today, word),
+        },
+        ["in", offset @ ..] => add_offset(today, &offset.concat()),
+        offset => add_offset(today, &offset.concat()),
+    }
+}
+
+/// # Works out which day a phrase looking back means
+/// The counterpart of [`parse_due`] for reports, so weekdays are the last one to have come,
+/// including today, and `last` always skips today. Also takes ISO dates, `today`,
+/// `yesterday`, `week` and `month` for the start of the current ones, and offsets like `3d`
+/// or `2w` for that long ago.
+pub fn parse_since(phrase: &str, today: NaiveDate) -> Option<NaiveDate> {
+    let phrase = phrase.trim().to_lowercase();
+    if let Ok(date) = NaiveDate::parse_from_str(&phrase, DUE_FORMAT) {
+        return Some(date);
+    }
+
+    let phrase = phrase.replace(['-', '_'], " ");
+    let words: Vec<&str> = phrase.split_whitespace().collect();
+    match words.as_slice() {
+        ["today"] => Some(today),
+        ["yesterday"] => today.pred_opt(),
+        ["week"] => last_weekday(today, Weekday::Mon, 0),
+        ["month"] => today.with_day(1),
+        ["last", day] => last_weekday(today, parse_weekday(day)?, 1),
+        [word] if parse_weekday(word).is_some() => last_weekday(today, parse_weekday(word)?, 0),
+        offset => {
+            let offset = offset.concat();
+            let ahead = add_offset(today, offset.trim_end_matches("ago"))?;
+            today.checked_sub_signed(ahead - today)
+        }
+    }
+}
+
+/// The last `weekday` at least `skip` days before today, if there's still one that early.
+fn last_weekday(today: NaiveDate, weekday: Weekday, skip: i64) -> Option<NaiveDate> {
+    let mut date = today.checked_sub_signed(Duration::days(skip))?;
+    while date.weekday() != weekday {
+        date = date.pred_opt()?;
+    }
+    Some(date)
+}
+
+pub fn parse_weekday(word: &str) -> Option<Weekday> {
+    match word {
+        "mon" | "monday" => Some(Weekday::Mon),
+        "tue" | "tues" | "tuesday" => Some(Weekday::Tue),
+        "wed" | "wednesday" => Some(Weekday::Wed),
+        "thu" | "thurs" | "thursday" => Some(Weekday::Thu),
+        "fri" | "friday" => Some(Weekday::Fri),
+        "sat" | "saturday" => Some(Weekday::Sat),
+        "sun" | "sunday" => Some(Weekday::Sun),
+        _ => None,
+    }
+}
+
+/// The first `weekday` at least `skip` days after today, if there's still one that late.
+fn next_weekday(today: NaiveDate, weekday: Weekday, skip: i64) -> Option<NaiveDate> {
+    let mut date = today.checked_add_signed(Duration::days(skip))?;
+    while date.weekday() != weekday {
+        date = date.succ_opt()?;
+    }
+    Some(date)
+}
+
+/// Offsets are a count followed by a unit, like `3d`, `2weeks`, or `1month`. Offsets too big
+/// for a date to reach give None.
+fn add_offset(today: NaiveDate, offset: &str) -> Option<NaiveDate> {
+    let split = offset.find(|character: char| !character.is_ascii_digit())?;
+    let count: i64 = offset[..split].parse().ok()?;
+
+    let days = match &offset[split..] {
+        "d" | "day" | "days" => count,
+        "w" | "week" | "weeks" => count.checked_mul(7)?,
+        "m" | "month" | "months" => return add_months(today, u32::try_from(count).ok()?),
+        _ => return None,
+    };
+    if days > MAX_OFFSET_DAYS {
+        return None;
+    }
+    today.checked_add_signed(Duration::days(days))
+}
+
+/// # Moves a date forward by whole months
+/// Days that don't exist in the new month, like the 31st of April, become its last day.
+pub fn add_months(date: NaiveDate, months: u32) -> Option<NaiveDate> {
+    let month_index = date.month0().checked_add(months)?;
+    let year = date
+        .year()
+        .checked_add(i32::try_from(month_index / 12).ok()?)?;
+    let month = month_index % 12 + 1;
+
+    (1..=date.day())
+        .rev()
+        .find_map(|day| NaiveDate::from_ymd_opt(year, month, day))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parses_phrases() {
+        // A Wednesday
+        let today = NaiveDate::from_ymd_opt(2022, 3, 16).unwrap();
+        let day = |month, day| NaiveDate::from_ymd_opt(2022, month, day);
+
+        assert_eq!(parse_due("2022-04-01", today), day(4, 1));
+        assert_eq!(parse_due("today", today), day(3, 16));
+        assert_eq!(parse_due("Tomorrow", today), day(3, 17));
+        assert_eq!(parse_due("friday", today), day(3, 18));
+        assert_eq!(parse_due("wed", today), day(3, 16));
+        assert_eq!(parse_due("next wed", today), day(3, 23));
+        assert_eq!(parse_due("next-mon", today), day(3, 21));
+        assert_eq!(parse_due("in 3d", today), day(3, 19));
+        assert_eq!(parse_due("in 2 weeks", today), day(3, 30));
+        assert_eq!(parse_due("1m", today), day(4, 16));
+        assert_eq!(parse_due("someday", today), None);
+        assert_eq!(parse_due("in 3 fortnights", today), None);
+    }
+
+    #[test]
+    fn test_huge_offsets_are_not_dates() {
+        let today = NaiveDate::from_ymd_opt(2022, 3, 16).unwrap();
+
+        assert_eq!(parse_due("in-99999999999d", today), None);
+        assert_eq!(parse_due("99999999999w", today), None);
+        assert_eq!(parse_due("in 999999999999999999999 days", today), None);
+        // This used to wrap around to no months at all
+        assert_eq!(parse_due("4294967296m", today), None);
+        assert_eq!(parse_due("4294967295m", today), None);
+    }
+
+    #[test]
+    fn test_parses_phrases_looking_back() {
+        // A Wednesday
+        let today = NaiveDate::from_ymd_opt(2022, 3, 16).unwrap();
+        let day = |month, day| NaiveDate::from_ymd_opt(2022, month, day);
+
+        assert_eq!(parse_since("monday", today), day(3, 14));
+        assert_eq!(parse_since("wed", today), day(3, 16));
+        assert_eq!(parse_since("last wed", today), day(3, 9));
+        assert_eq!(parse_since("week", today), day(3, 14));
+        assert_eq!(parse_since("month", today), day(3, 1));
+        assert_eq!(parse_since("3d", today), day(3, 13));
+        assert_eq!(parse_since("2 weeks ago", today), day(3, 2));
+        assert_eq!(parse_since("someday", today), None);
+        assert_eq!(parse_since("99999999999d", today), None);
+        assert_eq!(parse_since("99999999 days ago", today), None);
+    }
+
+    #[test]
+    fn test_months_keep_to_the_end_of_the_month() {
+        let date = NaiveDate::from_ymd_opt(2022, 1, 31).unwrap();
+
+        assert_eq!(add_months(date, 1), NaiveDate::from_ymd_opt(2022, 2, 28));
+        assert_eq!(add_months(date, 12), NaiveDate::from_ymd_opt(2023, 1, 31));
+    }
+}
@@ -1,25 +1,232 @@
+use std::{error, fmt, io, path};
+
 use colored::*;
 
-fn error_mark() -> ColoredString {
+pub(super) fn error_mark() -> ColoredString {
     "✖".red()
 }
 
-pub fn no_id_error() -> String {
-    format!(" {} No id was provided in input", error_mark())
+pub(super) fn join_ids(ids: &[u64], separator: &str) -> String {
+    let ids: Vec<String> = ids.iter().map(u64::to_string).collect();
+    ids.join(separator)
+}
+
+/// # Everything that can go wrong while running a command
+/// The errors only hold what went wrong, [`TaskerError::render`] turns them into what's shown
+/// to the user, and [`TaskerError::exit_code`] gives every kind of error its own exit code so
+/// scripts can tell them apart.
+#[derive(Debug)]
+pub enum TaskerError {
+    /// No item has the given id
+    UnknownId(String),
+    /// The command needs an `@id` but none was given
+    MissingId,
+    /// The command takes a single `@id` but more were given
+    TooManyIds,
+    /// Priorities can only be 1, 2, or 3
+    InvalidPriority,
+    /// The date couldn't be made sense of
+    InvalidDate(String),
+    /// The recurrence rule couldn't be made sense of
+    InvalidRecurrence(String),
+    /// The task is waiting on others that are still open
+    Blocked { id: u64, by: Vec<u64> },
+    /// Linking the tasks would leave them waiting on each other, going around these ids
+    DependencyCycle(Vec<u64>),
+    /// The estimate isn't a length of time
+    InvalidEstimate(String),
+    /// The start of a uuid matches more than one item
+    AmbiguousId(String),
+    /// The selector, like `@work` or `done`, didn't pick out any items
+    NothingSelected(String),
+    /// The user said no when asked to go ahead
+    Cancelled,
+    /// Reading or writing a file, or talking to another program, failed
+    Io {
+        location: path::PathBuf,
+        source: io::Error,
+    },
+    /// A file we read didn't hold what we expected
+    Parse {
+        location: path::PathBuf,
+        source: serde_json::Error,
+    },
+}
+
+impl TaskerError {
+    pub fn io(location: impl Into<path::PathBuf>, source: io::Error) -> TaskerError {
+        TaskerError::Io {
+            location: location.into(),
+            source,
+        }
+    }
+
+    pub fn parse(location: impl Into<path::PathBuf>, source: serde_json::Error) -> TaskerError {
+        TaskerError::Parse {
+            location: location.into(),
+            source,
+        }
+    }
+
+    /// # The exit code for this kind of error
+    /// 1 is left for anything we don't know about, and 2 is what clap uses for bad arguments.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            TaskerError::UnknownId(_) => 3,
+            TaskerError::MissingId => 4,
+            TaskerError::TooManyIds => 5,
+            TaskerError::InvalidPriority => 6,
+            TaskerError::Io { .. } => 7,
+            TaskerError::Parse { .. } => 8,
+            TaskerError::InvalidDate(_) => 9,
+            TaskerError::InvalidRecurrence(_) => 10,
+            TaskerError::Blocked { .. } => 11,
+            TaskerError::DependencyCycle(_) => 12,
+            TaskerError::InvalidEstimate(_) => 13,
+            TaskerError::AmbiguousId(_) => 14,
+            TaskerError::NothingSelected(_) => 15,
+            TaskerError::Cancelled => 16,
+        }
+    }
+
+    /// # Formats the error for the terminal
+    pub fn render(&self) -> String {
+        match self {
+            TaskerError::UnknownId(id) => format!(
+                " {} Unable to find item with id: {}",
+                error_mark(),
+                id.dimmed()
+            ),
+            TaskerError::Io { location, source } => format!(
+                " {} Couldn't use {}: {}",
+                error_mark(),
+                location.display(),
+                source.to_string().dimmed()
+            ),
+            TaskerError::Parse { location, source } => format!(
+                " {} Couldn't parse {}: {}",
+                error_mark(),
+                location.display(),
+                source.to_string().dimmed()
+            ),
+            _ => format!(" {} {}", error_mark(), self),
+        }
+    }
 }
 
-pub fn too_many_ids_error() -> String {
-    format!(" {} More than one id was given as input", error_mark())
+impl fmt::Display for TaskerError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TaskerError::UnknownId(id) => write!(f, "Unable to find item with id: {}", id),
+            TaskerError::MissingId => write!(f, "No id was provided in input"),
+            TaskerError::TooManyIds => write!(f, "More than one id was given as input"),
+            TaskerError::InvalidPriority => write!(f, "Priority can only be 1, 2, or 3"),
+            TaskerError::InvalidDate(date) => write!(
+                f,
+                "Couldn't tell what day {:?} is, try a date like 2022-03-14, tomorrow, fri, or in 3d",
+                date
+            ),
+            TaskerError::InvalidRecurrence(rule) => write!(
+                f,
+                "Couldn't tell how often {:?} is, try daily, weekly, mon,thu, monthly:15, or every-3d",
+                rule
+            ),
+            TaskerError::Blocked { id, by } => write!(
+                f,
+                "Item {} is blocked by {}, use --force to go ahead anyway",
+                id,
+                join_ids(by, ", ")
+            ),
+            TaskerError::DependencyCycle(cycle) => write!(
+                f,
+                "That would leave the items waiting on each other: {}",
+                join_ids(cycle, " → ")
+            ),
+            TaskerError::InvalidEstimate(estimate) => write!(
+                f,
+                "Couldn't tell how long {:?} is, try an estimate like 2h, 45m, or 1h30m",
+                estimate
+            ),
+            TaskerError::AmbiguousId(id) => write!(
+                f,
+                "More than one item has a uuid starting with {}, give more of it",
+                id
+            ),
+            TaskerError::NothingSelected(selector) => write!(f, "No items match {}", selector),
+            TaskerError::Cancelled => write!(f, "Nothing was changed"),
+            TaskerError::Io { location, source } => {
+                write!(f, "Couldn't use {}: {}", location.display(), source)
+            }
+            TaskerError::Parse { location, source } => {
+                write!(f, "Couldn't parse {}: {}", location.display(), source)
+            }
+        }
+    }
 }
 
-pub fn no_index(missing_id: String) -> String {
-    format!(
-        " {} Unable to find item with id: {}",
-        error_mark(),
-        missing_id.dimmed()
-    )
+impl error::Error for TaskerError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            TaskerError::Io { source, .. } => Some(source),
+            TaskerError::Parse { source, .. } => Some(source),
+            _ => None,
+        }
+    }
 }
 
-pub fn invalid_priority() -> String {
-    format!(" {} Priority can only be 1, 2, or 3", error_mark())
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_every_error_has_its_own_exit_code() {
+        let parse_error = serde_json::from_str::<u64>("x").unwrap_err();
+        let errors = [
+            (TaskerError::UnknownId("9".into()), 3),
+            (TaskerError::MissingId, 4),
+            (TaskerError::TooManyIds, 5),
+            (TaskerError::InvalidPriority, 6),
+            (TaskerError::io("x", io::ErrorKind::NotFound.into()), 7),
+            (TaskerError::parse("x", parse_error), 8),
+            (TaskerError::InvalidDate("someday".into()), 9),
+            (TaskerError::InvalidRecurrence("sometimes".into()), 10),
+            (TaskerError::Blocked { id: 1, by: vec![2] }, 11),
+            (TaskerError::DependencyCycle(vec![1, 2, 1]), 12),
+            (TaskerError::InvalidEstimate("ages".into()), 13),
+            (TaskerError::AmbiguousId("abcd".into()), 14),
+            (TaskerError::NothingSelected("@work".into()), 15),
+            (TaskerError::Cancelled, 16),
+        ];
+
+        for (index, (error, code)) in errors.iter().enumerate() {
+            assert_eq!(error.exit_code(), *code, "{:?}", error);
+            assert_eq!(*code, index as i32 + 3);
+        }
+    }
+
+    #[test]
+    fn test_render() {
+        let blocked = TaskerError::Blocked {
+            id: 4,
+            by: vec![1, 2],
+        };
+        assert_eq!(
+            blocked.render(),
+            format!(
+                " {} Item 4 is blocked by 1, 2, use --force to go ahead anyway",
+                error_mark()
+            )
+        );
+
+        let io = TaskerError::io("storage.json", io::ErrorKind::NotFound.into());
+        assert_eq!(
+            io.render(),
+            format!(
+                " {} Couldn't use storage.json: {}",
+                error_mark(),
+                "entity not found".dimmed()
+            )
+        );
+        assert!(error::Error::source(&io).is_some());
+    }
 }
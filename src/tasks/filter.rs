@@ -0,0 +1,200 @@
+use chrono::Local;
+
+use super::view::{render_boards, with_overview};
+use super::*;
+use crate::global_settings::Settings;
+
+/// # Attributes an item can be listed by
+/// These follow the names Taskbook accepts for `--list`, with a few aliases for each.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Attribute {
+    Task,
+    Note,
+    Pending,
+    Progress,
+    Done,
+    Starred,
+    Priority(u8),
+    /// Has a due date and isn't done yet
+    Due,
+    /// Was due before today and isn't done yet
+    Overdue,
+    /// Is waiting on other tasks
+    Blocked,
+}
+
+impl Attribute {
+    pub fn parse(term: &str) -> Option<Attribute> {
+        match term.to_lowercase().as_str() {
+            "task" | "tasks" | "todo" => Some(Attribute::Task),
+            "note" | "notes" => Some(Attribute::Note),
+            "pending" | "unchecked" | "incomplete" => Some(Attribute::Pending),
+            "progress" | "started" | "begun" => Some(Attribute::Progress),
+            "done" | "checked" | "complete" => Some(Attribute::Done),
+            "star" | "starred" => Some(Attribute::Starred),
+            "normal" => Some(Attribute::Priority(1)),
+            "medium" => Some(Attribute::Priority(2)),
+            "high" => Some(Attribute::Priority(3)),
+            "due" => Some(Attribute::Due),
+            "overdue" | "late" => Some(Attribute::Overdue),
+            "blocked" | "waiting" => Some(Attribute::Blocked),
+            _ => None,
+        }
+    }
+
+    pub fn matches(&self, task: &Task) -> bool {
+        match self {
+            Attribute::Task => !task.is_note(),
+            Attribute::Note => task.is_note(),
+            Attribute::Pending => !task.is_note() && !task.is_complete(),
+            Attribute::Progress => task.in_progress(),
+            Attribute::Done => task.is_complete(),
+            Attribute::Starred => task.is_starred(),
+            Attribute::Priority(level) => task.get_priority() == Some(*level),
+            Attribute::Due => task.get_due().is_some() && !task.is_complete(),
+            Attribute::Blocked => !task.get_blockers().is_empty(),
+            Attribute::Overdue => {
+                let today = Local::now().date_naive();
+                task.get_due().is_some_and(|due| due < today) && !task.is_complete()
+            }
+        }
+    }
+}
+
+/// # The filter behind `--list`
+/// Every attribute has to hold for an item to be listed. Any term that isn't an attribute is
+/// taken as a board name, and only those boards are shown when at least one is given.
+pub struct Filter {
+    attributes: Vec<Attribute>,
+    boards: Vec<String>,
+}
+
+impl Filter {
+    pub fn new(input: Vec<&str>) -> Filter {
+        let mut attributes: Vec<Attribute> = Vec::new();
+        let mut boards: Vec<String> = Vec::new();
+
+        for term in input.iter().flat_map(|value| value.split_whitespace()) {
+            match Attribute::parse(term) {
+                Some(attribute) => attributes.push(attribute),
+                None => boards.push(board_name(term)),
+            }
+        }
+
+        Filter { attributes, boards }
+    }
+
+    pub fn matches(&self, task: &Task) -> bool {
+        let on_board = self.boards.is_empty()
+            || task
+                .get_boards()
+                .iter()
+                .any(|board| self.boards.contains(board));
+
+        on_board
+            && self
+                .attributes
+                .iter()
+                .all(|attribute| attribute.matches(task))
+    }
+}
+
+/// Boards are stored with an `@` in front of them, except for the default board.
+pub(super) fn board_name(term: &str) -> String {
+    if term.eq_ignore_ascii_case("myboard") {
+        "My Board".into()
+    } else if term.starts_with('@') {
+        term.into()
+    } else {
+        format!("@{}", term)
+    }
+}
+
+impl TaskList {
+    pub fn list_view(&self, input: Vec<&str>, settings: &Settings) -> String {
+        let filter = Filter::new(input);
+
+        let listed: Vec<&Task> = self
+            .tasks
+            .values()
+            .filter(|task| filter.matches(task))
+            .collect();
+
+        // Asking for completed items overrides hiding them
+        let show_completed =
+            settings.show_completed() || filter.attributes.contains(&Attribute::Done);
+        let output = render_boards(&listed, &filter.boards, show_completed);
+
+        with_overview(output, &listed, settings)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn task(description: &str, boards: Vec<&str>, is_note: bool) -> Task {
+        let boards = boards.into_iter().map(String::from).collect();
+        Task::new(description.into(), boards, 0, is_note)
+    }
+
+    #[test]
+    fn test_attributes_and_boards_combine() {
+        let mut done = task("Ship it", vec!["@coding"], false);
+        done.flip_flag(TaskFlag::Check);
+        let pending = task("Patch issue", vec!["@coding"], false);
+        let elsewhere = task("Buttercream", vec![], false);
+
+        let filter = Filter::new(vec!["pending", "coding"]);
+        assert!(filter.matches(&pending));
+        assert!(!filter.matches(&done));
+        assert!(!filter.matches(&elsewhere));
+        assert!(Filter::new(vec!["myboard"]).matches(&elsewhere));
+    }
+
+    #[test]
+    fn test_notes_are_never_pending() {
+        let note = task("Remember this", vec![], true);
+
+        assert!(Filter::new(vec!["notes"]).matches(&note));
+        assert!(!Filter::new(vec!["pending"]).matches(&note));
+        assert!(!Filter::new(vec!["normal"]).matches(&note));
+    }
+
+    #[test]
+    fn test_due_skips_done_items() {
+        let today = Local::now().date_naive();
+        let mut late = task("Taxes", vec![], false);
+        late.set_due(today.pred_opt());
+        let mut soon = task("Report", vec![], false);
+        soon.set_due(today.succ_opt());
+        let mut done = task("Slides", vec![], false);
+        done.set_due(today.pred_opt());
+        done.flip_flag(TaskFlag::Check);
+
+        let due = Filter::new(vec!["due"]);
+        assert!(due.matches(&late) && due.matches(&soon) && !due.matches(&done));
+        let overdue = Filter::new(vec!["overdue"]);
+        assert!(overdue.matches(&late) && !overdue.matches(&soon) && !overdue.matches(&done));
+    }
+
+    #[test]
+    fn test_hidden_items_still_count() {
+        let settings = r#"{"taskbookDirectory": "~", "displayCompleteTasks": false,
+            "displayProgressOverview": false}"#;
+        let settings = Settings::new(settings.into()).unwrap();
+        let mut list = TaskList::new(String::new()).unwrap();
+        list.new_entry(vec!["@coding", "Ship", "it"], false)
+            .unwrap();
+        list.new_entry(vec!["@coding", "Patch", "issue"], false)
+            .unwrap();
+        list.tasks.get_mut(&0).unwrap().flip_flag(TaskFlag::Check);
+
+        let listed = list.list_view(vec!["coding"], &settings);
+        assert!(listed.contains("[1/2]"));
+        assert!(listed.contains("Patch issue") && !listed.contains("Ship it"));
+
+        let done = list.list_view(vec!["done"], &settings);
+        assert!(done.contains("[1/1]") && done.contains("Ship it"));
+    }
+}
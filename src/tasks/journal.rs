@@ -0,0 +1,212 @@
+use std::collections::BTreeSet;
+use std::fmt;
+
+use chrono::Local;
+use colored::*;
+use serde::{Deserialize, Serialize};
+
+use super::*;
+
+/// How many commands can be undone before the oldest ones are forgotten.
+const MAX_ENTRIES: usize = 100;
+
+/// # Which of the two lists a change was made to
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ListName {
+    Storage,
+    Archive,
+}
+
+/// # One item as it was before and after a command
+/// An item that didn't exist on one side of the command is None there.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct Change {
+    list: ListName,
+    id: u64,
+    before: Option<Task>,
+    after: Option<Task>,
+}
+
+/// # Everything a single command changed
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct Entry {
+    operation: String,
+    timestamp: i64,
+    changes: Vec<Change>,
+}
+
+/// # The history of commands that changed the lists
+/// Entries are kept oldest first. The last `undone` of them have been undone and can be
+/// redone, until a new command is recorded over them.
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct Journal {
+    entries: Vec<Entry>,
+    undone: usize,
+}
+
+impl Journal {
+    pub fn new(journal_json: String) -> Result<Journal, serde_json::Error> {
+        if journal_json.is_empty() {
+            Ok(Journal::default())
+        } else {
+            serde_json::from_str(&journal_json)
+        }
+    }
+
+    /// # Records what a command did to the lists
+    /// The lists are compared from before and after the command, and every item that differs
+    /// is kept in the entry. Gives back false, recording nothing, when nothing changed.
+    pub fn record(
+        &mut self,
+        operation: String,
+        before: (&TaskList, &TaskList),
+        after: (&TaskList, &TaskList),
+    ) -> bool {
+        let mut changes = diff(ListName::Storage, before.0, after.0);
+        changes.extend(diff(ListName::Archive, before.1, after.1));
+        if changes.is_empty() {
+            return false;
+        }
+
+        let kept = self.entries.len() - self.undone;
+        self.entries.truncate(kept);
+        self.undone = 0;
+        self.entries.push(Entry {
+            operation,
+            timestamp: Local::now().timestamp_millis(),
+            changes,
+        });
+
+        if self.entries.len() > MAX_ENTRIES {
+            let overflow = self.entries.len() - MAX_ENTRIES;
+            self.entries.drain(..overflow);
+        }
+        true
+    }
+
+    /// # Puts the items changed by the last command back how they were
+    pub fn undo(&mut self, task_list: &mut TaskList, archive: &mut TaskList) -> String {
+        if self.undone == self.entries.len() {
+            return format!(" {} Nothing to undo", "!".yellow());
+        }
+
+        self.undone += 1;
+        let entry = &self.entries[self.entries.len() - self.undone];
+        for change in entry.changes.iter().rev() {
+            let list = pick(change.list, task_list, archive);
+            list.put(change.id, change.before.clone());
+        }
+
+        format!(" {} Undid: {}", check_mark(), entry.operation.dimmed())
+    }
+
+    /// # Makes the last undone command's changes again
+    pub fn redo(&mut self, task_list: &mut TaskList, archive: &mut TaskList) -> String {
+        if self.undone == 0 {
+            return format!(" {} Nothing to redo", "!".yellow());
+        }
+
+        let entry = &self.entries[self.entries.len() - self.undone];
+        self.undone -= 1;
+        for change in &entry.changes {
+            let list = pick(change.list, task_list, archive);
+            list.put(change.id, change.after.clone());
+        }
+
+        format!(" {} Redid: {}", check_mark(), entry.operation.dimmed())
+    }
+}
+
+impl TaskList {
+    /// Sets the item under `id`, removing it when there's no item to set.
+    fn put(&mut self, id: u64, task: Option<Task>) {
+        match task {
+            Some(task) => self.tasks.insert(id, task),
+            None => self.tasks.remove(&id),
+        };
+    }
+}
+
+fn pick<'a>(
+    list: ListName,
+    task_list: &'a mut TaskList,
+    archive: &'a mut TaskList,
+) -> &'a mut TaskList {
+    match list {
+        ListName::Storage => task_list,
+        ListName::Archive => archive,
+    }
+}
+
+fn diff(list: ListName, before: &TaskList, after: &TaskList) -> Vec<Change> {
+    let ids: BTreeSet<&u64> = before.tasks.keys().chain(after.tasks.keys()).collect();
+
+    ids.into_iter()
+        .filter(|id| before.tasks.get(id) != after.tasks.get(id))
+        .map(|id| Change {
+            list,
+            id: *id,
+            before: before.tasks.get(id).cloned(),
+            after: after.tasks.get(id).cloned(),
+        })
+        .collect()
+}
+
+impl fmt::Display for Journal {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let pretty = serde_json::to_string_pretty(&self).expect("Failed to process the journal!");
+        write!(f, "{}", pretty)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_undo_and_redo_a_delete() {
+        let mut task_list = TaskList::new(String::new()).unwrap();
+        let mut archive = TaskList::new(String::new()).unwrap();
+        task_list.new_entry(vec!["Keep", "me"], false).unwrap();
+        let mut journal = Journal::default();
+
+        let before = (task_list.clone(), archive.clone());
+        task_list
+            .move_tasks_between_lists(&mut archive, Some(vec!["0"]), false)
+            .unwrap();
+        assert!(journal.record(
+            "--delete 0".into(),
+            (&before.0, &before.1),
+            (&task_list, &archive)
+        ));
+
+        journal.undo(&mut task_list, &mut archive);
+        assert_eq!(task_list.tasks.len(), 1);
+        assert!(archive.tasks.is_empty());
+
+        journal.redo(&mut task_list, &mut archive);
+        assert!(task_list.tasks.is_empty());
+        assert_eq!(archive.tasks.len(), 1);
+    }
+
+    #[test]
+    fn test_journal_is_bounded() {
+        let mut journal = Journal::default();
+        let empty = TaskList::new(String::new()).unwrap();
+        let mut list = TaskList::new(String::new()).unwrap();
+
+        for count in 0..MAX_ENTRIES + 5 {
+            let before = list.clone();
+            list.new_entry(vec!["Item"], false).unwrap();
+            journal.record(
+                format!("--task {}", count),
+                (&before, &empty),
+                (&list, &empty),
+            );
+        }
+
+        assert_eq!(journal.entries.len(), MAX_ENTRIES);
+        assert_eq!(journal.entries[0].operation, "--task 5");
+    }
+}
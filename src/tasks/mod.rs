@@ -1,22 +1,34 @@
-pub use task::TaskFlag;
+pub use errors::TaskerError;
+pub use journal::Journal;
+pub use select::take_target;
 use task::*;
-
-pub use modify::*;
-pub use view::*;
+pub use task::{check_mark, TaskFlag};
 
 use std::collections::BTreeMap;
 use std::fmt;
 
 use serde::{Deserialize, Serialize};
 
+mod depend;
+mod due;
 mod errors;
+mod filter;
+mod journal;
 mod modify;
+mod recur;
+mod search;
+mod select;
 mod task;
+mod track;
+mod validate;
 mod view;
 
+/// # A list of items keyed by their id
+/// The ids are kept as numbers so that they sort the way they read. Taskbook stores them as
+/// strings, which serde turns them back into when the list is written out.
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct TaskList {
-    tasks: BTreeMap<String, Task>,
+    tasks: BTreeMap<u64, Task>,
 }
 
 impl TaskList {
@@ -32,6 +44,17 @@ impl TaskList {
             }
         }
     }
+
+    /// # Gives every item without a uuid one
+    /// Lists written before uuids existed, or by Taskbook, don't have them. Gives back whether
+    /// any were missing, so the list can be saved with them.
+    pub fn assign_uuids(&mut self) -> bool {
+        let mut assigned = false;
+        for task in self.tasks.values_mut() {
+            assigned |= task.ensure_uuid();
+        }
+        assigned
+    }
 }
 
 impl fmt::Display for TaskList {
@@ -41,3 +64,219 @@ impl fmt::Display for TaskList {
         write!(f, "{}", pretty)
     }
 }
+
+/// # Builds an item the way Taskbook stores it, for tests
+/// It starts as an open task called "Item {id}" on "My Board", and `fields` are laid over
+/// that, so a test only spells out what it cares about.
+#[cfg(test)]
+pub(crate) fn item_json(id: u64, fields: serde_json::Value) -> serde_json::Value {
+    let mut item = serde_json::json!({
+        "_id": id, "_date": "", "_timestamp": 0, "description": format!("Item {}", id),
+        "isStarred": false, "boards": ["My Board"], "_isTask": true, "isComplete": false,
+        "inProgress": false, "priority": 1
+    });
+    if let serde_json::Value::Object(fields) = fields {
+        item.as_object_mut().unwrap().extend(fields);
+    }
+    item
+}
+
+/// # Stores items from [`item_json`] under their ids, the way a list file does
+#[cfg(test)]
+pub(crate) fn list_json(items: impl IntoIterator<Item = serde_json::Value>) -> String {
+    let list: serde_json::Map<String, serde_json::Value> = items
+        .into_iter()
+        .map(|item| (item["_id"].to_string(), item))
+        .collect();
+    serde_json::Value::Object(list).to_string()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::global_settings::Settings;
+
+    fn list_of(count: u64) -> TaskList {
+        let mut list = TaskList::new(String::new()).unwrap();
+        for id in 0..count {
+            list.new_entry(vec!["Item", &id.to_string()], false)
+                .unwrap();
+        }
+        list
+    }
+
+    #[test]
+    fn test_ids_sort_numerically() {
+        let list = list_of(120);
+        let ids: Vec<u64> = list.tasks.keys().copied().collect();
+
+        assert_eq!(ids, (0..120).collect::<Vec<u64>>());
+        assert_eq!(list.get_new_id(), 120);
+    }
+
+    #[test]
+    fn test_new_id_fills_gaps_past_nine() {
+        let mut list = list_of(120);
+        list.tasks.remove(&11);
+        list.tasks.remove(&104);
+
+        assert_eq!(list.get_new_id(), 11);
+        list.new_entry(vec!["Refill"], false).unwrap();
+        assert_eq!(list.get_new_id(), 104);
+    }
+
+    #[test]
+    fn test_json_keeps_string_keys() {
+        let list = list_of(101);
+        let json = list.to_string();
+        assert!(json.contains("\"100\": {"));
+
+        let reloaded = TaskList::new(json).unwrap();
+        assert_eq!(reloaded.tasks.len(), 101);
+        assert_eq!(reloaded.tasks[&100].get_id(), 100);
+    }
+
+    #[test]
+    fn test_checking_a_recurring_task_schedules_the_next() {
+        let mut list = list_of(1);
+        let mut archive = TaskList::new(String::new()).unwrap();
+        let settings = Settings::new(String::new()).unwrap();
+        list.new_entry(vec!["Bins", "@home", "repeat:daily"], false)
+            .unwrap();
+        list.flip_task_flag(vec!["1"], TaskFlag::Star, &mut archive, &settings)
+            .unwrap();
+
+        list.flip_task_flag(vec!["1"], TaskFlag::Check, &mut archive, &settings)
+            .unwrap();
+
+        let next = &list.tasks[&2];
+        assert_eq!(next.get_description(), "Bins");
+        assert!(!next.is_complete() && next.is_starred());
+        assert_eq!(next.get_boards(), vec!["@home".to_string()]);
+        assert_eq!(next.get_due(), chrono::Local::now().date_naive().succ_opt());
+        assert!(archive.tasks[&0].is_complete());
+        assert!(archive.tasks[&0].get_recurrence().is_none());
+    }
+
+    #[test]
+    fn test_subtasks_complete_their_parent() {
+        let mut list = list_of(1);
+        let mut archive = TaskList::new(String::new()).unwrap();
+        let settings = Settings::new(String::new()).unwrap();
+        list.add_subtask(vec!["@0", "Draft"]).unwrap();
+        list.add_subtask(vec!["@0", "Review"]).unwrap();
+        list.add_subtask(vec!["@0", "Publish"]).unwrap();
+
+        list.move_tasks_between_lists(&mut archive, Some(vec!["0.3"]), false)
+            .unwrap();
+        list.flip_task_flag(vec!["0.1"], TaskFlag::Check, &mut archive, &settings)
+            .unwrap();
+        assert_eq!(list.tasks[&0].subtask_progress(), (1, 2));
+        assert!(!list.tasks[&0].is_complete());
+
+        list.flip_task_flag(vec!["0.2"], TaskFlag::Check, &mut archive, &settings)
+            .unwrap();
+        assert!(list.tasks[&0].is_complete());
+        assert!(archive.tasks.is_empty());
+
+        list.flip_task_flag(vec!["0.1"], TaskFlag::Check, &mut archive, &settings)
+            .unwrap();
+        assert!(!list.tasks[&0].is_complete());
+
+        let missing = list.flip_task_flag(vec!["0.3"], TaskFlag::Check, &mut archive, &settings);
+        assert!(matches!(missing, Err(TaskerError::UnknownId(_))));
+    }
+
+    #[test]
+    fn test_checking_a_subtask_keeps_its_parent_checked() {
+        let mut list = list_of(1);
+        let mut archive = TaskList::new(String::new()).unwrap();
+        let settings = Settings::new(String::new()).unwrap();
+        for description in ["Draft", "Review", "Publish"] {
+            list.add_subtask(vec!["@0", description]).unwrap();
+        }
+        list.flip_task_flag(vec!["0"], TaskFlag::Check, &mut archive, &settings)
+            .unwrap();
+
+        list.flip_task_flag(vec!["0.1"], TaskFlag::Check, &mut archive, &settings)
+            .unwrap();
+        assert!(list.tasks[&0].is_complete());
+
+        list.flip_task_flag(vec!["0.1"], TaskFlag::Check, &mut archive, &settings)
+            .unwrap();
+        assert!(!list.tasks[&0].is_complete());
+    }
+
+    #[test]
+    fn test_archived_tasks_stop_tracking_time() {
+        let mut list = list_of(1);
+        let mut archive = TaskList::new(String::new()).unwrap();
+        let settings = Settings::new(String::new()).unwrap();
+        list.flip_task_flag(vec!["0"], TaskFlag::Begin, &mut archive, &settings)
+            .unwrap();
+
+        list.move_tasks_between_lists(&mut archive, Some(vec!["0"]), false)
+            .unwrap();
+
+        let now = chrono::Local::now().timestamp_millis();
+        let archived = &archive.tasks[&0];
+        assert_eq!(
+            archived.tracked_time(now),
+            archived.tracked_time(now + 3_600_000)
+        );
+    }
+
+    #[test]
+    fn test_merging_renumbers_items_and_their_blockers() {
+        let mut list = list_of(2);
+        let mut other = list_of(4);
+        other.block(vec!["@2", "0", "1"]).unwrap();
+        other.block(vec!["@3", "2"]).unwrap();
+        other.tasks.remove(&1);
+
+        let (count, renumbered) = list.merge(other.clone());
+
+        assert_eq!(count, 3);
+        assert_eq!(renumbered, [(0, 2), (2, 3), (3, 4)]);
+        assert_eq!(list.tasks[&2].get_uuid(), other.tasks[&0].get_uuid());
+        // The blocker on item 1 was never merged, so it's dropped
+        assert_eq!(list.tasks[&3].get_blockers(), [2]);
+        assert_eq!(list.tasks[&4].get_blockers(), [3]);
+
+        assert_eq!(list.merge(other), (0, Vec::new()));
+        assert_eq!(list.tasks.len(), 5);
+    }
+
+    #[test]
+    fn test_uuids_stay_with_items_and_pick_them_out() {
+        let mut list = list_of(3);
+        let mut archive = TaskList::new(String::new()).unwrap();
+        let uuid = list.tasks[&1].get_uuid().to_string();
+
+        list.move_tasks_between_lists(&mut archive, Some(vec![&uuid[..8]]), false)
+            .unwrap();
+        assert_eq!(archive.tasks[&0].get_uuid(), uuid);
+        archive
+            .move_tasks_between_lists(&mut list, Some(vec![&uuid]), true)
+            .unwrap();
+        assert_eq!(list.find_id(&uuid[..8].to_uppercase()).unwrap(), 1);
+        assert_eq!(list.find_id("2").unwrap(), 2);
+
+        let mut json = list.to_string();
+        for id in [0, 2] {
+            let old = list.tasks[&id].get_uuid();
+            json = json.replace(old, &format!("abcd-{}", id));
+        }
+        let list = TaskList::new(json).unwrap();
+        assert!(matches!(
+            list.find_id("abcd"),
+            Err(TaskerError::AmbiguousId(_))
+        ));
+        assert_eq!(list.find_id("abcd-2").unwrap(), 2);
+
+        let json = list.to_string().replace("abcd-2", "12345678-2");
+        let list = TaskList::new(json).unwrap();
+        assert_eq!(list.find_id("1234").unwrap(), 1234);
+        assert_eq!(list.find_id("12345678-").unwrap(), 2);
+    }
+}
@@ -1,21 +1,38 @@
+use chrono::{Local, NaiveDate};
 use colored::*;
 
+use super::due::parse_due;
+use super::errors::join_ids;
+use super::recur::Recurrence;
+use super::select::take_target;
+use super::track::{format_duration, parse_estimate};
 use super::*;
-use errors;
+use crate::global_settings::Settings;
 
 use std::str;
 
+/// Shorter uuid prefixes would be too easy to mix up with ids.
+const MIN_UUID_PREFIX: usize = 4;
+
 impl TaskList {
-    fn get_tasks_by_ids(&mut self, ids: Vec<&str>) -> Result<Vec<Task>, String> {
-        let mut results: Vec<Task> = Vec::new();
+    fn get_tasks_by_ids(&mut self, ids: Vec<&str>) -> Result<Vec<Task>, TaskerError> {
+        let ids: Vec<u64> = self.find_existing_ids(ids)?;
 
-        for id in &ids {
-            match self.tasks.remove(&id.to_string()) {
-                Some(value) => results.push(value),
-                None => return Err(errors::no_index(id.to_string())),
+        Ok(ids.iter().filter_map(|id| self.tasks.remove(id)).collect())
+    }
+
+    /// # Gets the descriptions of the given items
+    /// They're joined by newlines, in the order the ids were given in.
+    pub fn get_descriptions(&self, ids: Vec<&str>) -> Result<String, TaskerError> {
+        let mut descriptions: Vec<&str> = Vec::new();
+
+        for id in self.find_ids(ids)? {
+            match self.tasks.get(&id) {
+                Some(task) => descriptions.push(task.get_description()),
+                None => return Err(TaskerError::UnknownId(id.to_string())),
             }
         }
-        Ok(results)
+        Ok(descriptions.join("\n"))
     }
 
     fn get_special_ids_from_args(&self, input: Vec<&str>) -> (Vec<String>, String) {
@@ -33,51 +50,143 @@ impl TaskList {
         (special_ids, new_sentence.join(" "))
     }
 
-    fn get_task_id_from_input(&self, input: Vec<&str>) -> Result<(String, Vec<String>), String> {
-        let mut special_id: String = String::new();
-        let mut new_sentence: Vec<String> = Vec::new();
+    pub(super) fn get_task_id_from_input(
+        &self,
+        input: Vec<&str>,
+    ) -> Result<(u64, Vec<String>), TaskerError> {
+        let (id, words): (&str, Vec<&str>) = take_target(input)?;
 
-        for word in input {
-            if word.starts_with('@') && special_id.is_empty() {
-                special_id = word.into();
-            } else if word.starts_with('@') && !special_id.is_empty() {
-                return Err(errors::too_many_ids_error());
-            } else {
-                new_sentence.push(word.into());
+        Ok((
+            self.find_id(id)?,
+            words.into_iter().map(String::from).collect(),
+        ))
+    }
+
+    /// # Works out which item an id given by the user means
+    /// That's either the number shown next to the item, or the start of its uuid, which stays
+    /// the same as the item moves between lists. Numbers are always taken as the shown id, even
+    /// when no item has it, since a uuid prefix of only digits is too easy to mistype for one.
+    /// Those are given back as they are, for the caller to report. Uuids need at least a few
+    /// characters, and enough of them to only match one item.
+    pub(super) fn find_id(&self, id: &str) -> Result<u64, TaskerError> {
+        if let Ok(number) = id.parse::<u64>() {
+            return Ok(number);
+        }
+
+        if id.len() >= MIN_UUID_PREFIX {
+            let prefix = id.to_lowercase();
+            let matches: Vec<u64> = self
+                .tasks
+                .values()
+                .filter(|task| task.get_uuid().starts_with(&prefix))
+                .map(|task| task.get_id())
+                .collect();
+            match matches.as_slice() {
+                [] => (),
+                [only] => return Ok(*only),
+                _ => return Err(TaskerError::AmbiguousId(id.to_string())),
             }
         }
 
-        if special_id.is_empty() {
-            return Err(errors::no_id_error());
+        Err(TaskerError::UnknownId(id.to_string()))
+    }
+
+    fn find_ids(&self, ids: Vec<&str>) -> Result<Vec<u64>, TaskerError> {
+        ids.into_iter().map(|id| self.find_id(id)).collect()
+    }
+
+    /// Like [`TaskList::find_ids`], but every one of the items has to be in the list.
+    fn find_existing_ids(&self, ids: Vec<&str>) -> Result<Vec<u64>, TaskerError> {
+        let ids: Vec<u64> = self.find_ids(ids)?;
+
+        match ids.iter().find(|id| !self.tasks.contains_key(id)) {
+            Some(missing) => Err(TaskerError::UnknownId(missing.to_string())),
+            None => Ok(ids),
         }
+    }
 
-        let id: String = str::replace(special_id.as_str(), "@", "");
+    /// # Splits a subtask id like `4.2` into its parent's id and its number
+    /// The parent can be given by its uuid too.
+    fn find_subtask_id(&self, id: &str) -> Option<(u64, usize)> {
+        let (parent, number) = id.split_once('.')?;
+        Some((self.find_id(parent).ok()?, number.parse().ok()?))
+    }
 
-        Ok((id, new_sentence))
+    /// # Lists the uuids of the given items
+    /// Each is shown after the item's id, in the order the ids were given in.
+    pub fn get_uuids(&self, ids: Vec<&str>) -> Result<String, TaskerError> {
+        let mut output: Vec<String> = Vec::new();
+
+        for id in self.find_ids(ids)? {
+            match self.tasks.get(&id) {
+                Some(task) => output.push(format!(
+                    " {} {}",
+                    format!("{}.", id).dimmed(),
+                    task.get_uuid()
+                )),
+                None => return Err(TaskerError::UnknownId(id.to_string())),
+            }
+        }
+        Ok(output.join("\n"))
     }
 
-    pub fn flip_task_flag(&mut self, ids: Vec<&str>, flag: TaskFlag) -> Result<String, String> {
+    /// # Flips a flag on the given items
+    /// Subtasks, given like `4.2`, can only be checked. Checking a recurring task moves it to
+    /// the archive and adds its next occurrence in its place.
+    pub fn flip_task_flag(
+        &mut self,
+        ids: Vec<&str>,
+        flag: TaskFlag,
+        archive: &mut TaskList,
+        settings: &Settings,
+    ) -> Result<String, TaskerError> {
         let mut marked_tasks: Vec<String> = Vec::new();
         let mut unmarked_tasks: Vec<String> = Vec::new();
+        let mut finished: Vec<u64> = Vec::new();
+
+        let (subtask_ids, ids): (Vec<&str>, Vec<&str>) =
+            ids.into_iter().partition(|id| id.contains('.'));
+        let subtask_output = match (flag, subtask_ids.first()) {
+            (_, None) => String::new(),
+            (TaskFlag::Check, Some(_)) => {
+                self.flip_subtasks(subtask_ids, settings.complete_parents(), &mut finished)?
+            }
+            (_, Some(id)) => return Err(TaskerError::UnknownId(id.to_string())),
+        };
 
-        let mut found_tasks: Vec<Task> = self.get_tasks_by_ids(ids)?;
+        let found_tasks: Vec<Task> = self.get_tasks_by_ids(ids)?;
 
-        while !found_tasks.is_empty() {
-            let mut task = found_tasks.pop().unwrap();
+        for mut task in found_tasks.into_iter().rev() {
             match task.flip_flag(flag) {
-                Some(value) => match value {
-                    true => marked_tasks.push(task.get_id().to_string()),
-                    false => unmarked_tasks.push(task.get_id().to_string()),
-                },
+                Some(true) => marked_tasks.push(task.get_id().to_string()),
+                Some(false) => unmarked_tasks.push(task.get_id().to_string()),
                 None => (),
             };
-            self.tasks.insert(task.get_id().to_string(), task);
+            if let (TaskFlag::Check, true) = (flag, task.is_complete()) {
+                finished.push(task.get_id());
+            }
+            self.tasks.insert(task.get_id(), task);
+        }
+
+        // Checked tasks are no longer in the way of anything, nor waiting on anything
+        for id in &finished {
+            self.release(*id);
+            if let Some(task) = self.tasks.get_mut(id) {
+                task.clear_blockers();
+            }
+        }
+
+        let mut recurred: Vec<String> = Vec::new();
+        for id in finished.into_iter().rev() {
+            if let Some(next_id) = self.recur(id, archive) {
+                recurred.push(format!("{} → {}", id, next_id));
+            }
         }
 
         let string_of_marked = marked_tasks.join(", ").dimmed();
         let string_of_unmarked = unmarked_tasks.join(", ").dimmed();
 
-        let output = if !marked_tasks.is_empty() {
+        let mut output = if !marked_tasks.is_empty() {
             match flag {
                 TaskFlag::Begin => {
                     format!(" {} Started task(s): {}\n", check_mark(), string_of_marked)
@@ -90,6 +199,16 @@ impl TaskList {
         } else {
             String::new()
         };
+        output = subtask_output + &output;
+
+        if !recurred.is_empty() {
+            output = format!(
+                "{} {} Archived and scheduled the next one: {}\n",
+                output,
+                "↻".green(),
+                recurred.join(", ").dimmed()
+            );
+        }
 
         if !unmarked_tasks.is_empty() {
             match flag {
@@ -117,58 +236,229 @@ impl TaskList {
         }
     }
 
+    /// # Checks or unchecks subtasks
+    /// Parents that end up checked because of it are added to `finished`.
+    fn flip_subtasks(
+        &mut self,
+        ids: Vec<&str>,
+        complete_parent: bool,
+        finished: &mut Vec<u64>,
+    ) -> Result<String, TaskerError> {
+        let mut checked: Vec<String> = Vec::new();
+        let mut unchecked: Vec<String> = Vec::new();
+
+        for (id, number) in self.find_subtasks(&ids)? {
+            let task = self.tasks.get_mut(&id).unwrap();
+            let was_complete = task.is_complete();
+
+            match task.flip_subtask(number, complete_parent) {
+                Some(true) => checked.push(format!("{}.{}", id, number)),
+                Some(false) => unchecked.push(format!("{}.{}", id, number)),
+                None => (),
+            }
+            if task.is_complete() && !was_complete {
+                checked.push(id.to_string());
+                finished.push(id);
+            } else if was_complete && !task.is_complete() {
+                unchecked.push(id.to_string());
+            }
+        }
+
+        let mut output = String::new();
+        if !checked.is_empty() {
+            output = format!(
+                " {} Checked: {}\n",
+                check_mark(),
+                checked.join(", ").dimmed()
+            );
+        }
+        if !unchecked.is_empty() {
+            output = format!(
+                "{} {} Unchecked: {}\n",
+                output,
+                check_mark(),
+                unchecked.join(", ").dimmed()
+            );
+        }
+        Ok(output)
+    }
+
+    /// # Looks up subtask ids like `4.2`
+    /// Either every one of them exists, or nothing is given back but the first that doesn't.
+    fn find_subtasks(&self, ids: &[&str]) -> Result<Vec<(u64, usize)>, TaskerError> {
+        ids.iter()
+            .map(|id| match self.find_subtask_id(id) {
+                Some((parent, number))
+                    if self
+                        .tasks
+                        .get(&parent)
+                        .is_some_and(|task| task.has_subtask(number)) =>
+                {
+                    Ok((parent, number))
+                }
+                _ => Err(TaskerError::UnknownId(id.to_string())),
+            })
+            .collect()
+    }
+
+    /// # Adds a subtask to the end of a task's list
+    pub fn add_subtask(&mut self, input: Vec<&str>) -> Result<String, TaskerError> {
+        let (id, words): (u64, Vec<String>) = self.get_task_id_from_input(input)?;
+
+        match self.tasks.get_mut(&id) {
+            Some(task) => {
+                let number = task.add_subtask(words.join(" "));
+                Ok(format!(
+                    " {} Created subtask: {}",
+                    check_mark(),
+                    format!("{}.{}", id, number).dimmed()
+                ))
+            }
+            None => Err(TaskerError::UnknownId(id.to_string())),
+        }
+    }
+
+    /// # Replaces a finished recurring task with its next occurrence
+    /// The finished one goes to the archive. Gives back the id of the next occurrence, or
+    /// None when the task doesn't recur.
+    fn recur(&mut self, id: u64, archive: &mut TaskList) -> Option<u64> {
+        let next_id = self.get_new_id();
+        let next = self.tasks.get(&id)?.next_occurrence(next_id)?;
+        self.tasks.insert(next_id, next);
+
+        // Only the next occurrence carries the rule on, so restoring this one can't fork it
+        let mut finished = self.tasks.remove(&id)?;
+        let archived_id = archive.get_new_id();
+        finished.set_id(archived_id);
+        finished.set_recurrence(None);
+        finished.clear_blockers();
+        archive.tasks.insert(archived_id, finished);
+        Some(next_id)
+    }
+
     pub fn move_tasks_between_lists(
         &mut self,
         other_list: &mut TaskList,
         ids: Option<Vec<&str>>,
         restore: bool,
-    ) -> Result<String, String> {
+    ) -> Result<String, TaskerError> {
+        // Subtasks aren't archived, deleting one just takes it out of its task
+        let (subtask_ids, ids): (Vec<&str>, Vec<&str>) = ids
+            .unwrap_or_default()
+            .into_iter()
+            .partition(|id| id.contains('.'));
+        let mut subtasks = match subtask_ids.first() {
+            Some(id) if restore => return Err(TaskerError::UnknownId(id.to_string())),
+            _ => self.find_subtasks(&subtask_ids)?,
+        };
+
         // If we're not provided any to move, we're just going to
         // assume that we're moving all the completed tasks over.
-        let holder = self.tasks.clone();
-        let ids = match ids {
-            Some(ids) => ids,
-            None => {
-                let mut to_delete: Vec<&str> = Vec::new();
-                for (id, task) in holder.iter() {
-                    match task.is_complete() {
-                        true => to_delete.push(id),
-                        false => (),
-                    }
-                }
-                to_delete
-            }
+        let ids: Vec<u64> = match ids {
+            ids if !ids.is_empty() || !subtasks.is_empty() => self.find_ids(ids)?,
+            _ => self
+                .tasks
+                .iter()
+                .filter(|(_, task)| task.is_complete())
+                .map(|(id, _)| *id)
+                .collect(),
         };
 
         let mut found_tasks: Vec<Task> = Vec::new();
 
         for id in &ids {
-            match self.tasks.remove(&id.to_string()) {
+            match self.tasks.remove(id) {
                 Some(value) => found_tasks.push(value),
-                None => return Err(errors::no_index(id.to_string())),
+                None => return Err(TaskerError::UnknownId(id.to_string())),
             }
         }
 
-        for task in found_tasks.iter_mut() {
+        // Links only make sense within a list, so they don't go along with the task, and
+        // time isn't tracked while it's away
+        for mut task in found_tasks {
+            self.release(task.get_id());
+            task.clear_blockers();
+            task.stop_session();
             let new_id: u64 = other_list.get_new_id();
             task.set_id(new_id);
-            other_list.tasks.insert(new_id.to_string(), task.clone());
+            other_list.tasks.insert(new_id, task);
         }
 
-        let moved_ids = ids.join(", ").dimmed();
+        // Taking the highest numbers out first keeps the others where they were
+        subtasks.sort_by(|a, b| b.cmp(a));
+        subtasks.dedup();
+        for (id, number) in &subtasks {
+            if let Some(task) = self.tasks.get_mut(id) {
+                task.remove_subtask(*number);
+            }
+        }
+
+        let mut moved_ids: Vec<String> = ids.iter().map(|id| id.to_string()).collect();
+        moved_ids.extend(subtask_ids.iter().map(|id| id.to_string()));
+        let moved_ids = moved_ids.join(", ").dimmed();
 
         if restore {
-            Ok(format!(" {} Deleted item(s): {}", check_mark(), moved_ids))
+            Ok(format!(" {} Restored item(s): {}", check_mark(), moved_ids))
         } else {
-            Ok(format!(" {} Restore item(s): {}", check_mark(), moved_ids))
+            Ok(format!(" {} Deleted item(s): {}", check_mark(), moved_ids))
+        }
+    }
+
+    /// # Merges the items of another list into this one
+    /// Items keep their id unless it's already taken, in which case they get a new one. Items
+    /// that are already in this list, going by their uuid, or their creation time and
+    /// description for items without one, are skipped so that merging the same list twice
+    /// doesn't duplicate anything. Gives back the number of items merged and the ids that had
+    /// to change, as (old, new) pairs. Blockers are renumbered along with the items, and links
+    /// to items that weren't merged are dropped.
+    pub fn merge(&mut self, other: TaskList) -> (usize, Vec<(u64, u64)>) {
+        let mut merged: BTreeMap<u64, u64> = BTreeMap::new();
+        let mut renumbered: Vec<(u64, u64)> = Vec::new();
+
+        for (_, mut task) in other.tasks {
+            let duplicate = self.tasks.values().any(|existing| match task.get_uuid() {
+                "" => {
+                    existing.get_timestamp() == task.get_timestamp()
+                        && existing.get_description() == task.get_description()
+                }
+                uuid => existing.get_uuid() == uuid,
+            });
+            if duplicate {
+                continue;
+            }
+            task.ensure_uuid();
+
+            let old_id = task.get_id();
+            if self.tasks.contains_key(&old_id) {
+                let new_id = self.get_new_id();
+                task.set_id(new_id);
+                renumbered.push((old_id, new_id));
+            }
+
+            merged.insert(old_id, task.get_id());
+            self.tasks.insert(task.get_id(), task);
         }
+
+        for id in merged.values() {
+            let task = self.tasks.get_mut(id).unwrap();
+            let blockers: Vec<u64> = task.get_blockers().to_vec();
+            task.clear_blockers();
+            for blocker in blockers.iter().filter_map(|blocker| merged.get(blocker)) {
+                task.add_blocker(*blocker);
+            }
+        }
+
+        (merged.len(), renumbered)
     }
 
-    fn get_new_id(&self) -> u64 {
+    /// # Finds the lowest id that isn't in use
+    /// Since the ids are kept in numeric order, the first one that doesn't line up with its
+    /// position in the list is where the gap is.
+    pub(super) fn get_new_id(&self) -> u64 {
         let mut potential_id: u64 = 0;
 
-        for (id, _) in &self.tasks {
-            if !potential_id.to_string().eq(id) {
+        for id in self.tasks.keys() {
+            if potential_id != *id {
                 return potential_id;
             }
             potential_id += 1;
@@ -177,8 +467,8 @@ impl TaskList {
         potential_id
     }
 
-    pub fn edit(&mut self, input: Vec<&str>) -> Result<String, String> {
-        let (id, words): (String, Vec<String>) = self.get_task_id_from_input(input)?;
+    pub fn edit(&mut self, input: Vec<&str>) -> Result<String, TaskerError> {
+        let (id, words): (u64, Vec<String>) = self.get_task_id_from_input(input)?;
 
         let sentence: String = words.join(" ");
 
@@ -187,77 +477,222 @@ impl TaskList {
             Ok(format!(
                 " {} Updated description of item: {}",
                 check_mark(),
-                id.dimmed()
+                id.to_string().dimmed()
             ))
         } else {
-            Err(errors::no_index(id))
+            Err(TaskerError::UnknownId(id.to_string()))
         }
     }
 
-    pub fn move_to_board(&mut self, input: Vec<&str>) -> Result<String, String> {
-        let (id, words): (String, Vec<String>) = self.get_task_id_from_input(input)?;
+    /// # Puts items on the given boards instead of the ones they're on
+    pub fn move_to_board(
+        &mut self,
+        ids: Vec<&str>,
+        boards: Vec<&str>,
+    ) -> Result<String, TaskerError> {
+        let ids: Vec<u64> = self.find_existing_ids(ids)?;
 
-        let mut new_boards: Vec<String> = Vec::new();
-        for word in words {
-            new_boards.push(format!("@{}", word));
+        let new_boards: Vec<String> = boards.iter().map(|board| format!("@{}", board)).collect();
+        for id in &ids {
+            self.tasks
+                .get_mut(id)
+                .unwrap()
+                .set_boards(new_boards.clone());
         }
 
-        if self.tasks.contains_key(&id) {
-            self.tasks.get_mut(&id).unwrap().set_boards(new_boards);
-            Ok(format!(
-                " {} Updated description of item: {}",
+        Ok(format!(
+            " {} Moved item(s): {} to {}",
+            check_mark(),
+            join_ids(&ids, ", ").dimmed(),
+            new_boards.join(", ")
+        ))
+    }
+
+    pub fn priority(&mut self, ids: Vec<&str>, words: Vec<&str>) -> Result<String, TaskerError> {
+        let priority: u8 = match words.as_slice() {
+            [priority] => str::parse::<u8>(priority).map_err(|_| TaskerError::InvalidPriority)?,
+            _ => return Err(TaskerError::InvalidPriority),
+        };
+
+        let priority_text = match priority {
+            1 => "normal".green(),
+            2 => "medium".yellow(),
+            3 => "high".red(),
+            _ => return Err(TaskerError::InvalidPriority),
+        };
+
+        let ids: Vec<u64> = self.find_existing_ids(ids)?;
+        for id in &ids {
+            self.tasks.get_mut(id).unwrap().set_priority(priority);
+        }
+
+        Ok(format!(
+            " {} Updated priority of task(s): {} to {}",
+            check_mark(),
+            join_ids(&ids, ", ").dimmed(),
+            priority_text
+        ))
+    }
+
+    /// # Sets or clears when an item is due
+    /// Everything after the `@id` is read as the due date, with `none` clearing it.
+    pub fn due(&mut self, input: Vec<&str>) -> Result<String, TaskerError> {
+        let (id, words): (u64, Vec<String>) = self.get_task_id_from_input(input)?;
+        let phrase = words.join(" ");
+
+        let due = match phrase.as_str() {
+            "none" | "clear" => None,
+            _ => Some(parse_due_phrase(&phrase)?),
+        };
+
+        let task = match self.tasks.get_mut(&id) {
+            Some(task) => task,
+            None => return Err(TaskerError::UnknownId(id.to_string())),
+        };
+        task.set_due(due);
+
+        match due {
+            Some(date) => Ok(format!(
+                " {} Item {} is due {}",
                 check_mark(),
-                id.dimmed()
-            ))
-        } else {
-            Err(errors::no_index(id))
+                id.to_string().dimmed(),
+                date.format("%a %b %e %Y")
+            )),
+            None => Ok(format!(
+                " {} Removed the due date of item {}",
+                check_mark(),
+                id.to_string().dimmed()
+            )),
         }
     }
 
-    pub fn priority(&mut self, input: Vec<&str>) -> Result<String, String> {
-        let (id, words): (String, Vec<String>) = self.get_task_id_from_input(input)?;
+    /// # Sets or clears how long a task is expected to take
+    /// Everything after the `@id` is read as the estimate, like `2h` or `45m`, with `none`
+    /// clearing it.
+    pub fn estimate(&mut self, input: Vec<&str>) -> Result<String, TaskerError> {
+        let (id, words): (u64, Vec<String>) = self.get_task_id_from_input(input)?;
+        let text = words.concat();
+
+        let minutes = match text.as_str() {
+            "none" | "clear" => None,
+            _ => Some(parse_estimate_text(&text)?),
+        };
 
-        if words.len() > 1 || words.is_empty() {
-            return Err(errors::invalid_priority());
+        match self.tasks.get_mut(&id) {
+            Some(task) => task.set_estimate(minutes),
+            None => return Err(TaskerError::UnknownId(id.to_string())),
         }
 
-        let priority: u8 = match str::parse::<u8>(&words[0]) {
-            Ok(v) => v,
-            Err(_) => return Err(errors::invalid_priority()),
+        match minutes {
+            Some(minutes) => Ok(format!(
+                " {} Item {} should take {}",
+                check_mark(),
+                id.to_string().dimmed(),
+                format_duration(i64::from(minutes) * 60_000)
+            )),
+            None => Ok(format!(
+                " {} Removed the estimate of item {}",
+                check_mark(),
+                id.to_string().dimmed()
+            )),
+        }
+    }
+
+    /// # Sets or clears how often a task comes back
+    /// Everything after the `@id` is read as the rule, with `none` clearing it. A bare `weekly`
+    /// or `monthly` repeats on the day the task is due, or today if it has no due date.
+    pub fn repeat(&mut self, input: Vec<&str>) -> Result<String, TaskerError> {
+        let (id, words): (u64, Vec<String>) = self.get_task_id_from_input(input)?;
+        let phrase = words.join(" ");
+
+        let task = match self.tasks.get_mut(&id) {
+            Some(task) => task,
+            None => return Err(TaskerError::UnknownId(id.to_string())),
         };
 
-        let priority_text = match priority {
-            1 => "normal".green(),
-            2 => "medium".yellow(),
-            3 => "high".red(),
-            _ => return Err(errors::invalid_priority()),
+        let rule = match phrase.as_str() {
+            "none" | "clear" => None,
+            _ => Some(parse_rule(&phrase, task.get_due())?),
         };
+        task.set_recurrence(rule.as_ref());
 
-        if self.tasks.contains_key(&id) {
-            self.tasks.get_mut(&id).unwrap().set_priority(priority);
-            Ok(format!(
-                " {} Updated priority of task: {} to {}",
+        match rule {
+            Some(rule) => Ok(format!(
+                " {} Item {} repeats {}",
                 check_mark(),
-                id.dimmed(),
-                priority_text
-            ))
-        } else {
-            Err(errors::no_index(id))
+                id.to_string().dimmed(),
+                rule
+            )),
+            None => Ok(format!(
+                " {} Item {} no longer repeats",
+                check_mark(),
+                id.to_string().dimmed()
+            )),
         }
     }
 
-    pub fn new_entry(&mut self, input: Vec<&str>, is_note: bool) -> String {
-        let (boards, sentence): (Vec<String>, String) = self.get_special_ids_from_args(input);
+    /// # Adds a new task or note
+    /// The input is split into words, so boards, and `due:<when>`, `repeat:<rule>`, and
+    /// `est:<time>` words are picked out even when everything was quoted. Those are single
+    /// words, like `due:in-3d`, `repeat:mon,thu`, or `est:1h30m`.
+    pub fn new_entry(&mut self, input: Vec<&str>, is_note: bool) -> Result<String, TaskerError> {
+        let mut due: Option<NaiveDate> = None;
+        let mut repeat: Option<&str> = None;
+        let mut estimate: Option<u32> = None;
+        let mut words: Vec<&str> = Vec::new();
+        for word in input.iter().flat_map(|value| value.split_whitespace()) {
+            if let Some(phrase) = word.strip_prefix("due:") {
+                due = Some(parse_due_phrase(phrase)?);
+            } else if let Some(rule) = word.strip_prefix("repeat:") {
+                repeat = Some(rule);
+            } else if let Some(time) = word.strip_prefix("est:") {
+                estimate = Some(parse_estimate_text(time)?);
+            } else {
+                words.push(word);
+            }
+        }
+        // The rule is read last, since it can depend on the due date
+        let rule = match repeat {
+            Some(rule) => Some(parse_rule(rule, due)?),
+            None => None,
+        };
+
+        let (boards, sentence): (Vec<String>, String) = self.get_special_ids_from_args(words);
         let id = self.get_new_id();
         let id_string = id.to_string();
 
-        let new_entry = Task::new(sentence, boards, id, is_note);
-        self.tasks.insert(id_string.clone(), new_entry);
+        let mut new_entry = Task::new(sentence, boards, id, is_note);
+        new_entry.set_due(due);
+        new_entry.set_recurrence(rule.as_ref());
+        new_entry.set_estimate(estimate);
+        self.tasks.insert(id, new_entry);
 
         if is_note {
-            format!(" {} Created note: {}", check_mark(), id_string.dimmed())
+            Ok(format!(
+                " {} Created note: {}",
+                check_mark(),
+                id_string.dimmed()
+            ))
         } else {
-            format!(" {} Created task: {}", check_mark(), id_string.dimmed())
+            Ok(format!(
+                " {} Created task: {}",
+                check_mark(),
+                id_string.dimmed()
+            ))
         }
     }
 }
+
+fn parse_rule(rule: &str, due: Option<NaiveDate>) -> Result<Recurrence, TaskerError> {
+    let start = due.unwrap_or_else(|| Local::now().date_naive());
+    Recurrence::parse(rule, start).ok_or_else(|| TaskerError::InvalidRecurrence(rule.to_string()))
+}
+
+fn parse_estimate_text(text: &str) -> Result<u32, TaskerError> {
+    parse_estimate(text).ok_or_else(|| TaskerError::InvalidEstimate(text.to_string()))
+}
+
+fn parse_due_phrase(phrase: &str) -> Result<NaiveDate, TaskerError> {
+    parse_due(phrase, Local::now().date_naive())
+        .ok_or_else(|| TaskerError::InvalidDate(phrase.to_string()))
+}
@@ -0,0 +1,215 @@
+use std::fmt;
+
+use chrono::{Datelike, Duration, NaiveDate, Weekday};
+
+use super::due::{add_months, parse_weekday};
+
+const WEEKDAYS: [(Weekday, &str); 7] = [
+    (Weekday::Mon, "mon"),
+    (Weekday::Tue, "tue"),
+    (Weekday::Wed, "wed"),
+    (Weekday::Thu, "thu"),
+    (Weekday::Fri, "fri"),
+    (Weekday::Sat, "sat"),
+    (Weekday::Sun, "sun"),
+];
+
+/// The longest gap a rule can have, about a hundred years.
+const MAX_GAP_DAYS: u32 = 36_525;
+
+/// # How often a task comes back
+/// Rules are stored in the item as the text [`fmt::Display`] gives, which
+/// [`Recurrence::parse`] reads back.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Recurrence {
+    Daily,
+    /// On each of these days of the week
+    Weekly(Vec<Weekday>),
+    /// On this day of every month, or the last day for months that are shorter
+    Monthly(u32),
+    /// This many days after the last one
+    EveryDays(u32),
+}
+
+impl Recurrence {
+    /// # Reads a rule the way it's typed or stored
+    /// Takes `daily`, `weekly`, weekdays like `mon,thu` or `weekly:mon,thu`, `monthly` or
+    /// `monthly:15`, and gaps like `every:3d`, `every-2w`, or just `3d`. The bare `weekly` and
+    /// `monthly` repeat on the weekday or day of the month that `start` falls on.
+    pub fn parse(rule: &str, start: NaiveDate) -> Option<Recurrence> {
+        let rule = rule.trim().to_lowercase();
+        let (kind, detail) = match rule.split_once(':') {
+            Some((kind, detail)) => (kind, detail),
+            None => (rule.as_str(), ""),
+        };
+
+        match (kind, detail) {
+            ("daily", "") => Some(Recurrence::Daily),
+            ("weekly", "") => Some(Recurrence::Weekly(vec![start.weekday()])),
+            ("weekly", days) => parse_weekdays(days),
+            ("monthly", "") => Some(Recurrence::Monthly(start.day())),
+            ("monthly", day) => match day.parse() {
+                Ok(day) if (1..=31).contains(&day) => Some(Recurrence::Monthly(day)),
+                _ => None,
+            },
+            ("every", gap) => parse_gap(gap.trim_start_matches(['-', ' '])),
+            (rule, "") => match rule.strip_prefix("every") {
+                Some(gap) => parse_gap(gap.trim_start_matches(['-', ' '])),
+                None => parse_weekdays(rule).or_else(|| parse_gap(rule)),
+            },
+            _ => None,
+        }
+    }
+
+    /// # The first day after `date` the rule lands on
+    /// Gives back None if that day is past the last date there is.
+    pub fn next_after(&self, date: NaiveDate) -> Option<NaiveDate> {
+        match self {
+            Recurrence::Daily => date.succ_opt(),
+            Recurrence::Weekly(days) => {
+                let mut next = date.succ_opt()?;
+                // An empty list can't come from parsing, but shouldn't loop forever either
+                for _ in 0..6 {
+                    if days.contains(&next.weekday()) {
+                        break;
+                    }
+                    next = next.succ_opt()?;
+                }
+                Some(next)
+            }
+            Recurrence::Monthly(day) => {
+                let this_month = day_of_month(date, *day);
+                if this_month > date {
+                    Some(this_month)
+                } else {
+                    let next_month = add_months(date.with_day(1)?, 1)?;
+                    Some(day_of_month(next_month, *day))
+                }
+            }
+            Recurrence::EveryDays(count) => {
+                date.checked_add_signed(Duration::days(i64::from((*count).max(1))))
+            }
+        }
+    }
+}
+
+/// Weekdays are separated by commas, like `mon,thu`.
+fn parse_weekdays(days: &str) -> Option<Recurrence> {
+    let mut weekdays: Vec<Weekday> = Vec::new();
+
+    for name in days.split(',') {
+        let weekday = parse_weekday(name.trim())?;
+        if !weekdays.contains(&weekday) {
+            weekdays.push(weekday);
+        }
+    }
+
+    weekdays.sort_by_key(|weekday| weekday.num_days_from_monday());
+    Some(Recurrence::Weekly(weekdays))
+}
+
+/// Gaps are a count of days or weeks, like `3d`, `3 days`, or `2w`, up to [`MAX_GAP_DAYS`].
+fn parse_gap(gap: &str) -> Option<Recurrence> {
+    let gap: String = gap.split_whitespace().collect();
+    let split = gap.find(|character: char| !character.is_ascii_digit())?;
+    let count: u32 = gap[..split].parse().ok()?;
+
+    let days = match &gap[split..] {
+        "d" | "day" | "days" => count,
+        "w" | "week" | "weeks" => count.checked_mul(7)?,
+        _ => return None,
+    };
+    match days {
+        1..=MAX_GAP_DAYS => Some(Recurrence::EveryDays(days)),
+        _ => None,
+    }
+}
+
+/// The given day in the month of `date`, or the last day of that month if it's shorter.
+fn day_of_month(date: NaiveDate, day: u32) -> NaiveDate {
+    (1..=day)
+        .rev()
+        .find_map(|day| date.with_day(day))
+        .unwrap_or(date)
+}
+
+impl fmt::Display for Recurrence {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Recurrence::Daily => write!(f, "daily"),
+            Recurrence::Weekly(days) => {
+                let names: Vec<&str> = days
+                    .iter()
+                    .filter_map(|day| WEEKDAYS.iter().find(|(weekday, _)| weekday == day))
+                    .map(|(_, name)| *name)
+                    .collect();
+                write!(f, "weekly:{}", names.join(","))
+            }
+            Recurrence::Monthly(day) => write!(f, "monthly:{}", day),
+            Recurrence::EveryDays(count) => write!(f, "every:{}d", count),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parses_rules_and_reads_them_back() {
+        // A Wednesday
+        let start = NaiveDate::from_ymd_opt(2022, 3, 16).unwrap();
+
+        for (typed, stored) in [
+            ("daily", "daily"),
+            ("weekly", "weekly:wed"),
+            ("thu,Mon", "weekly:mon,thu"),
+            ("weekly:friday", "weekly:fri"),
+            ("monthly", "monthly:16"),
+            ("monthly:31", "monthly:31"),
+            ("every-3d", "every:3d"),
+            ("2w", "every:14d"),
+        ] {
+            let rule = Recurrence::parse(typed, start).unwrap();
+            assert_eq!(rule.to_string(), stored);
+            assert_eq!(Recurrence::parse(stored, start), Some(rule));
+        }
+
+        assert_eq!(Recurrence::parse("sometimes", start), None);
+        assert_eq!(Recurrence::parse("every:0d", start), None);
+        assert_eq!(Recurrence::parse("999999999w", start), None);
+        assert_eq!(Recurrence::parse("every:4000000000d", start), None);
+        assert_eq!(Recurrence::parse("every:36526d", start), None);
+    }
+
+    #[test]
+    fn test_next_after() {
+        let day = |month, day| NaiveDate::from_ymd_opt(2022, month, day).unwrap();
+
+        assert_eq!(Recurrence::Daily.next_after(day(3, 16)), Some(day(3, 17)));
+        let weekly = Recurrence::Weekly(vec![Weekday::Mon, Weekday::Thu]);
+        assert_eq!(weekly.next_after(day(3, 16)), Some(day(3, 17)));
+        assert_eq!(weekly.next_after(day(3, 17)), Some(day(3, 21)));
+        assert_eq!(
+            Recurrence::Monthly(31).next_after(day(1, 31)),
+            Some(day(2, 28))
+        );
+        assert_eq!(
+            Recurrence::Monthly(31).next_after(day(2, 28)),
+            Some(day(3, 31))
+        );
+        assert_eq!(
+            Recurrence::Monthly(10).next_after(day(3, 16)),
+            Some(day(4, 10))
+        );
+        assert_eq!(
+            Recurrence::EveryDays(3).next_after(day(3, 16)),
+            Some(day(3, 19))
+        );
+
+        let last = NaiveDate::MAX;
+        assert_eq!(Recurrence::EveryDays(MAX_GAP_DAYS).next_after(last), None);
+        assert_eq!(Recurrence::Daily.next_after(last), None);
+        assert_eq!(Recurrence::Monthly(1).next_after(last), None);
+    }
+}
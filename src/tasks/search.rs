@@ -0,0 +1,121 @@
+use colored::*;
+
+use super::errors::error_mark;
+use super::view::{render_boards, with_overview};
+use super::*;
+use crate::global_settings::Settings;
+
+/// # A search over the items of a task list
+/// The query is split into lowercase terms, and an item is only a hit when every term matches
+/// it. A term matches when it appears anywhere in the description or one of the subtasks,
+/// names one of the item's boards, or is the item's id. Terms starting with `@` only ever
+/// match board names.
+pub struct Query {
+    terms: Vec<String>,
+}
+
+impl Query {
+    pub fn new(input: Vec<&str>) -> Query {
+        let terms = input
+            .iter()
+            .flat_map(|value| value.split_whitespace())
+            .map(|term| term.to_lowercase())
+            .collect();
+
+        Query { terms }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.terms.is_empty()
+    }
+
+    pub fn matches(&self, task: &Task) -> bool {
+        let mut text = task.get_description().to_lowercase();
+        for subtask in task.get_subtask_descriptions() {
+            text = format!("{}\n{}", text, subtask.to_lowercase());
+        }
+
+        self.terms.iter().all(|term| {
+            if let Some(board) = term.strip_prefix('@') {
+                return matches_board(task, board);
+            }
+
+            text.contains(term.as_str())
+                || matches_board(task, term)
+                || task.get_id().to_string() == *term
+        })
+    }
+}
+
+/// Boards are stored with their `@` prefix, apart from the default board which has none.
+fn matches_board(task: &Task, term: &str) -> bool {
+    task.get_boards()
+        .iter()
+        .any(|board| board.trim_start_matches('@').to_lowercase() == term)
+}
+
+impl TaskList {
+    pub fn find(&self, input: Vec<&str>, settings: &Settings) -> String {
+        let query = Query::new(input);
+        if query.is_empty() {
+            return format!(" {} No search terms were given", error_mark());
+        }
+
+        let hits: Vec<&Task> = self
+            .tasks
+            .values()
+            .filter(|task| query.matches(task))
+            .collect();
+
+        if hits.is_empty() {
+            format!(
+                " {} No items matched: {}",
+                error_mark(),
+                query.terms.join(" ").dimmed()
+            )
+        } else {
+            with_overview(render_boards(&hits, &[], true), &hits, settings)
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn task(id: u64, description: &str, boards: Vec<&str>) -> Task {
+        let boards = boards.into_iter().map(String::from).collect();
+        Task::new(description.into(), boards, id, false)
+    }
+
+    #[test]
+    fn test_all_words_must_match() {
+        let hit = task(1, "Write the Documentation for release", vec![]);
+        let miss = task(2, "Write the changelog", vec![]);
+        let query = Query::new(vec!["write documentation"]);
+
+        assert!(query.matches(&hit));
+        assert!(!query.matches(&miss));
+    }
+
+    #[test]
+    fn test_matches_boards_and_ids() {
+        let item = task(12, "Patch issue", vec!["@coding"]);
+
+        assert!(Query::new(vec!["coding"]).matches(&item));
+        assert!(Query::new(vec!["@Coding"]).matches(&item));
+        assert!(Query::new(vec!["12"]).matches(&item));
+        assert!(!Query::new(vec!["@patch"]).matches(&item));
+    }
+
+    #[test]
+    fn test_matches_subtasks() {
+        let mut item = task(3, "Release", vec![]);
+        item.add_subtask("Tag the Version".into());
+        item.add_subtask("Write notes".into());
+
+        assert!(Query::new(vec!["version"]).matches(&item));
+        assert!(Query::new(vec!["release notes"]).matches(&item));
+        assert!(!Query::new(vec!["tag notes version changelog"]).matches(&item));
+    }
+}
@@ -0,0 +1,111 @@
+use super::filter::{board_name, Attribute};
+use super::*;
+
+impl TaskList {
+    /// # Expands selectors into the ids of the items they pick out
+    /// A selector can be an id or the start of a uuid, a range of ids like `3-7`, a board
+    /// like `@work`, or one of the attributes `--list` takes, like `done` or `starred`. Commas
+    /// join several into one, like `1,4,9`. Subtask ids like `4.2` are given back as they are.
+    /// Every selector has to pick out something, though ranges skip the ids that aren't in
+    /// use. The ids come back in the order they were selected, each only once.
+    pub fn select(&self, selectors: Vec<&str>) -> Result<Vec<String>, TaskerError> {
+        let mut selected: Vec<String> = Vec::new();
+
+        for selector in selectors.iter().flat_map(|value| value.split(',')) {
+            let ids: Vec<String> = match self.select_one(selector.trim())? {
+                Some(ids) => ids.iter().map(u64::to_string).collect(),
+                None => vec![selector.trim().to_string()],
+            };
+            if ids.is_empty() {
+                return Err(TaskerError::NothingSelected(selector.to_string()));
+            }
+
+            for id in ids {
+                if !selected.contains(&id) {
+                    selected.push(id);
+                }
+            }
+        }
+
+        Ok(selected)
+    }
+
+    /// Gives back None for selectors that stand for a single item, which are left for the
+    /// command to look up, so that its errors stay the same as with plain ids.
+    fn select_one(&self, selector: &str) -> Result<Option<Vec<u64>>, TaskerError> {
+        if let Some(board) = selector.strip_prefix('@') {
+            let board = board_name(board);
+            return Ok(Some(
+                self.ids_where(|task| task.get_boards().contains(&board)),
+            ));
+        }
+
+        if let Some((first, last)) = selector.split_once('-') {
+            if let (Ok(first), Ok(last)) = (first.parse::<u64>(), last.parse::<u64>()) {
+                let range = first.min(last)..=first.max(last);
+                return Ok(Some(self.ids_where(|task| range.contains(&task.get_id()))));
+            }
+        }
+
+        match Attribute::parse(selector) {
+            Some(attribute) => Ok(Some(self.ids_where(|task| attribute.matches(task)))),
+            None if selector.is_empty() => Err(TaskerError::MissingId),
+            None => Ok(None),
+        }
+    }
+
+    fn ids_where(&self, predicate: impl Fn(&Task) -> bool) -> Vec<u64> {
+        self.tasks
+            .values()
+            .filter(|task| predicate(task))
+            .map(|task| task.get_id())
+            .collect()
+    }
+}
+
+/// # Splits off the `@selector` naming the items a command changes
+/// Gives back the selector without its `@`, and the rest of the input split into words, so
+/// quoted input works too. A board is named with a second `@`, like `@@work`.
+pub fn take_target(input: Vec<&str>) -> Result<(&str, Vec<&str>), TaskerError> {
+    let mut target: Option<&str> = None;
+    let mut rest: Vec<&str> = Vec::new();
+
+    for word in input.into_iter().flat_map(str::split_whitespace) {
+        match (word.strip_prefix('@'), target) {
+            (Some(_), Some(_)) => return Err(TaskerError::TooManyIds),
+            (Some(selector), None) => target = Some(selector),
+            (None, _) => rest.push(word),
+        }
+    }
+
+    match target {
+        Some(target) => Ok((target, rest)),
+        None => Err(TaskerError::MissingId),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_selectors_expand_to_ids() {
+        let mut list = TaskList::new(String::new()).unwrap();
+        for description in ["One @work", "Two", "Three @work", "Four", "Five"] {
+            list.new_entry(description.split(' ').collect(), false)
+                .unwrap();
+        }
+        list.tasks.remove(&3);
+        list.tasks.get_mut(&4).unwrap().flip_flag(TaskFlag::Check);
+
+        assert_eq!(list.select(vec!["2-4"]).unwrap(), ["2", "4"]);
+        assert_eq!(list.select(vec!["4,1", "1.2"]).unwrap(), ["4", "1", "1.2"]);
+        assert_eq!(list.select(vec!["@work", "done"]).unwrap(), ["0", "2", "4"]);
+        assert_eq!(list.select(vec!["@myboard"]).unwrap(), ["1", "4"]);
+        assert!(matches!(
+            list.select(vec!["starred"]),
+            Err(TaskerError::NothingSelected(_))
+        ));
+        assert_eq!(take_target(vec!["@3-5", "2"]).unwrap(), ("3-5", vec!["2"]));
+    }
+}
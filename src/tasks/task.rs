@@ -1,14 +1,23 @@
 use serde::{Deserialize, Serialize};
 
-use chrono::Local;
+use super::due::{Urgency, DUE_FORMAT};
+use super::recur::Recurrence;
+use super::track::format_duration;
+use super::validate::Problem;
+use chrono::{Local, NaiveDate};
 use std::fmt;
 
 use colored::*;
+use uuid::Uuid;
 
 pub fn check_mark() -> ColoredString {
     "✓".green()
 }
 
+fn new_uuid() -> String {
+    Uuid::new_v4().to_string()
+}
+
 #[derive(Clone, Copy)]
 pub enum TaskFlag {
     Begin,
@@ -16,9 +25,36 @@ pub enum TaskFlag {
     Star,
 }
 
+/// # A step inside a task
+/// They're addressed by their parent's id and their place in the list, starting at one, like
+/// `4.2`.
+#[allow(non_snake_case)]
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct Subtask {
+    description: String,
+    isComplete: bool,
+}
+
+/// # A stretch of time spent on a task
+/// Times are milliseconds since the epoch, like `_timestamp`. The session is still running
+/// while it has no end.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct Session {
+    pub start: i64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub end: Option<i64>,
+}
+
+impl Session {
+    /// How long the session lasted, or has lasted up until `now`.
+    pub fn length(&self, now: i64) -> i64 {
+        (self.end.unwrap_or(now) - self.start).max(0)
+    }
+}
+
 // These are camelCase because I want compatibility with task book
 #[allow(non_snake_case)]
-#[derive(Deserialize, Serialize, Debug, Clone)]
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
 pub struct Task {
     _id: u64,
     _date: String,
@@ -30,6 +66,27 @@ pub struct Task {
     isComplete: Option<bool>,
     inProgress: Option<bool>,
     priority: Option<u8>,
+    /// Stays the same for as long as the item exists, unlike `_id`, which changes as the item
+    /// moves between lists. Items from before these were added get one when they're loaded.
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    taskerUuid: String,
+    /// Taskbook doesn't know about due dates, so this is named to keep out of its way
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    taskerDueDate: Option<String>,
+    /// The rule for when the task comes back, as written by [`Recurrence`]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    taskerRepeat: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    taskerSubtasks: Vec<Subtask>,
+    /// Ids of the tasks in the same list this one is waiting on
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    taskerBlockedBy: Vec<u64>,
+    /// Every start and pause of the task, oldest first
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    taskerSessions: Vec<Session>,
+    /// How many minutes the task is expected to take
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    taskerEstimate: Option<u32>,
 }
 
 impl Task {
@@ -60,18 +117,25 @@ impl Task {
             _id: id,
             _date: date,
             _timestamp: timestamp,
-            description: description,
+            description,
             isStarred: false,
-            boards: boards,
+            boards,
             _isTask: !is_note,
             isComplete: complete,
             inProgress: progress,
-            priority: priority,
+            priority,
+            taskerUuid: new_uuid(),
+            taskerDueDate: None,
+            taskerRepeat: None,
+            taskerSubtasks: Vec::new(),
+            taskerBlockedBy: Vec::new(),
+            taskerSessions: Vec::new(),
+            taskerEstimate: None,
         }
     }
 
     pub fn get_boards(&self) -> Vec<String> {
-        return self.boards.clone();
+        self.boards.clone()
     }
 
     pub fn set_boards(&mut self, boards: Vec<String>) {
@@ -79,53 +143,253 @@ impl Task {
     }
 
     pub fn get_id(&self) -> u64 {
-        return self._id;
+        self._id
     }
 
     pub fn set_id(&mut self, id: u64) {
         self._id = id;
     }
 
+    pub fn get_uuid(&self) -> &str {
+        &self.taskerUuid
+    }
+
+    /// # Gives the item a uuid if it's missing one
+    /// Gives back whether it was missing.
+    pub fn ensure_uuid(&mut self) -> bool {
+        if self.taskerUuid.is_empty() {
+            self.taskerUuid = new_uuid();
+            true
+        } else {
+            false
+        }
+    }
+
+    pub fn get_date(&self) -> &str {
+        &self._date
+    }
+
+    pub fn get_timestamp(&self) -> i64 {
+        self._timestamp
+    }
+
+    pub fn get_description(&self) -> &str {
+        &self.description
+    }
+
     pub fn set_description(&mut self, description: String) {
         self.description = description;
     }
 
     pub fn is_note(&self) -> bool {
-        return !self._isTask;
+        !self._isTask
     }
 
     pub fn is_complete(&self) -> bool {
-        match self.isComplete {
-            Some(value) => value,
-            None => false,
+        self.isComplete.unwrap_or_default()
+    }
+
+    pub fn in_progress(&self) -> bool {
+        self.inProgress.unwrap_or_default()
+    }
+
+    pub fn is_starred(&self) -> bool {
+        self.isStarred
+    }
+
+    pub fn get_priority(&self) -> Option<u8> {
+        self.priority
+    }
+
+    /// # The day the item is due, if it has one
+    /// Dates that can't be read are treated as missing, validation is what reports them.
+    pub fn get_due(&self) -> Option<NaiveDate> {
+        let due = self.taskerDueDate.as_ref()?;
+        NaiveDate::parse_from_str(due, DUE_FORMAT).ok()
+    }
+
+    pub fn set_due(&mut self, due: Option<NaiveDate>) {
+        self.taskerDueDate = due.map(|date| date.format(DUE_FORMAT).to_string());
+    }
+
+    /// # How often the task comes back, if it does
+    pub fn get_recurrence(&self) -> Option<Recurrence> {
+        let rule = self.taskerRepeat.as_ref()?;
+        Recurrence::parse(rule, Local::now().date_naive())
+    }
+
+    pub fn set_recurrence(&mut self, recurrence: Option<&Recurrence>) {
+        self.taskerRepeat = recurrence.map(|rule| rule.to_string());
+    }
+
+    /// # Makes the next occurrence of a recurring task
+    /// It's a fresh copy of this task under the given id, due on the first day the rule lands
+    /// on after both the current due date and today, so a late task doesn't come back already
+    /// overdue. Tasks without a due date count from today. Rules that would land past the last
+    /// date there is give None.
+    pub fn next_occurrence(&self, id: u64) -> Option<Task> {
+        let rule = self.get_recurrence()?;
+        let today = Local::now().date_naive();
+
+        let mut due = rule.next_after(self.get_due().unwrap_or(today))?;
+        while due <= today {
+            due = rule.next_after(due)?;
+        }
+
+        let fresh = Task::new(String::new(), Vec::new(), id, false);
+        let mut next = self.clone();
+        next._id = id;
+        next.taskerUuid = fresh.taskerUuid;
+        next._date = fresh._date;
+        next._timestamp = fresh._timestamp;
+        next.isComplete = Some(false);
+        next.inProgress = Some(false);
+        next.set_due(Some(due));
+        for subtask in &mut next.taskerSubtasks {
+            subtask.isComplete = false;
+        }
+        next.taskerSessions.clear();
+        Some(next)
+    }
+
+    /// The estimate in minutes, if the task has one.
+    pub fn get_estimate(&self) -> Option<u32> {
+        self.taskerEstimate
+    }
+
+    pub fn set_estimate(&mut self, minutes: Option<u32>) {
+        self.taskerEstimate = minutes;
+    }
+
+    pub fn get_sessions(&self) -> &[Session] {
+        &self.taskerSessions
+    }
+
+    /// # The time spent on the task so far
+    /// A running session counts up until `now`.
+    pub fn tracked_time(&self, now: i64) -> i64 {
+        self.taskerSessions
+            .iter()
+            .map(|session| session.length(now))
+            .sum()
+    }
+
+    fn start_session(&mut self) {
+        self.stop_session();
+        self.taskerSessions.push(Session {
+            start: Local::now().timestamp_millis(),
+            end: None,
+        });
+    }
+
+    /// Ends the running session, if there is one.
+    pub fn stop_session(&mut self) {
+        if let Some(session) = self.taskerSessions.last_mut() {
+            session.end.get_or_insert(Local::now().timestamp_millis());
         }
     }
 
+    pub fn get_blockers(&self) -> &[u64] {
+        &self.taskerBlockedBy
+    }
+
+    pub fn add_blocker(&mut self, id: u64) {
+        if !self.taskerBlockedBy.contains(&id) {
+            self.taskerBlockedBy.push(id);
+            self.taskerBlockedBy.sort_unstable();
+        }
+    }
+
+    pub fn remove_blocker(&mut self, id: u64) {
+        self.taskerBlockedBy.retain(|blocker| *blocker != id);
+    }
+
+    pub fn clear_blockers(&mut self) {
+        self.taskerBlockedBy.clear();
+    }
+
+    /// Adds a subtask to the end of the list, giving back its number.
+    pub fn add_subtask(&mut self, description: String) -> usize {
+        self.taskerSubtasks.push(Subtask {
+            description,
+            isComplete: false,
+        });
+        self.taskerSubtasks.len()
+    }
+
+    pub fn has_subtask(&self, number: usize) -> bool {
+        (1..=self.taskerSubtasks.len()).contains(&number)
+    }
+
+    pub fn remove_subtask(&mut self, number: usize) -> Option<Subtask> {
+        if self.has_subtask(number) {
+            Some(self.taskerSubtasks.remove(number - 1))
+        } else {
+            None
+        }
+    }
+
+    /// # Checks or unchecks a subtask
+    /// With `complete_parent`, a task is checked once all of its subtasks are, and unchecked
+    /// again if one of them is. Gives back whether the subtask is now checked.
+    pub fn flip_subtask(&mut self, number: usize, complete_parent: bool) -> Option<bool> {
+        let subtask = self.taskerSubtasks.get_mut(number.checked_sub(1)?)?;
+        subtask.isComplete = !subtask.isComplete;
+        let checked = subtask.isComplete;
+
+        if complete_parent && !self.is_note() {
+            let (done, total) = self.subtask_progress();
+            if !checked {
+                self.isComplete = Some(false);
+            } else if done == total {
+                self.isComplete = Some(true);
+                self.inProgress = Some(false);
+                self.stop_session();
+            }
+        }
+        Some(checked)
+    }
+
+    pub fn get_subtask_descriptions(&self) -> impl Iterator<Item = &str> {
+        self.taskerSubtasks
+            .iter()
+            .map(|subtask| subtask.description.as_str())
+    }
+
+    /// How many subtasks are checked, and how many there are.
+    pub fn subtask_progress(&self) -> (usize, usize) {
+        let done = self
+            .taskerSubtasks
+            .iter()
+            .filter(|subtask| subtask.isComplete)
+            .count();
+        (done, self.taskerSubtasks.len())
+    }
+
     pub fn flip_flag(&mut self, flag: TaskFlag) -> Option<bool> {
         match flag {
             TaskFlag::Begin => {
-                match self.inProgress {
-                    Some(progress) => match progress {
-                        true => self.inProgress = Some(false),
-                        false => {
-                            self.inProgress = Some(true);
-                            self.isComplete = Some(false);
-                        }
-                    },
-                    None => (),
+                if let Some(progress) = self.inProgress {
+                    if progress {
+                        self.inProgress = Some(false);
+                        self.stop_session();
+                    } else {
+                        self.inProgress = Some(true);
+                        self.isComplete = Some(false);
+                        self.start_session();
+                    }
                 };
                 self.inProgress
             }
             TaskFlag::Check => {
-                match self.isComplete {
-                    Some(complete) => match complete {
-                        true => self.isComplete = Some(false),
-                        false => {
-                            self.isComplete = Some(true);
-                            self.inProgress = Some(false);
-                        }
-                    },
-                    None => (),
+                if let Some(complete) = self.isComplete {
+                    if complete {
+                        self.isComplete = Some(false);
+                    } else {
+                        self.isComplete = Some(true);
+                        self.inProgress = Some(false);
+                        self.stop_session();
+                    }
                 }
                 self.isComplete
             }
@@ -139,52 +403,131 @@ impl Task {
     pub fn set_priority(&mut self, priority: u8) {
         self.priority = Some(priority);
     }
+
+    /// # Finds what's wrong with this item on its own
+    /// Problems between items, like duplicate ids, are left to the task list to find.
+    pub fn problems(&self) -> Vec<Problem> {
+        let mut problems: Vec<Problem> = Vec::new();
+
+        if let Some(priority) = self.priority {
+            if !(1..=3).contains(&priority) {
+                problems.push(Problem::InvalidPriority(priority));
+            }
+        }
+
+        let has_state = [
+            self.isComplete.is_some(),
+            self.inProgress.is_some(),
+            self.priority.is_some(),
+        ];
+        if self.is_note() && has_state.contains(&true) {
+            problems.push(Problem::NoteWithState);
+        } else if !self.is_note() && has_state.contains(&false) {
+            problems.push(Problem::TaskWithoutState);
+        }
+
+        if self.boards.is_empty() {
+            problems.push(Problem::NoBoards);
+        }
+
+        if let Some(due) = &self.taskerDueDate {
+            if self.get_due().is_none() {
+                problems.push(Problem::InvalidDueDate(due.clone()));
+            }
+        }
+
+        if let Some(rule) = &self.taskerRepeat {
+            if self.get_recurrence().is_none() {
+                problems.push(Problem::InvalidRecurrence(rule.clone()));
+            }
+        }
+
+        problems
+    }
+
+    /// # Fixes what [`Task::problems`] finds
+    /// Anything missing or out of place is reset to what a new item would have.
+    pub fn repair(&mut self) {
+        if self.is_note() {
+            self.isComplete = None;
+            self.inProgress = None;
+            self.priority = None;
+        } else {
+            self.isComplete.get_or_insert(false);
+            self.inProgress.get_or_insert(false);
+            if !(1..=3).contains(&self.priority.unwrap_or(0)) {
+                self.priority = Some(1);
+            }
+        }
+
+        if self.boards.is_empty() {
+            self.boards.push("My Board".into());
+        }
+
+        if self.get_due().is_none() {
+            self.taskerDueDate = None;
+        }
+
+        if self.get_recurrence().is_none() {
+            self.taskerRepeat = None;
+        }
+    }
+
+    /// # Shows when the item is due, coloured by how soon that is
+    /// Completed items have nothing left to be late for, so their date is just dimmed.
+    fn due_label(&self) -> ColoredString {
+        let due = match self.get_due() {
+            Some(due) => due,
+            None => return "".normal(),
+        };
+
+        if self.is_complete() {
+            return format!("due {} ", due.format("%a %b %-d")).dimmed();
+        }
+
+        match Urgency::of(due, Local::now().date_naive()) {
+            Urgency::Overdue(days) => format!("overdue {}d ", days).red(),
+            Urgency::Today => "due today ".yellow(),
+            Urgency::Soon(1) => "due tomorrow ".yellow(),
+            Urgency::Soon(days) => format!("due in {}d ", days).yellow(),
+            Urgency::Later => format!("due {} ", due.format("%a %b %-d")).dimmed(),
+        }
+    }
 }
 
-impl fmt::Display for Task {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+impl Task {
+    /// # The item on a line of its own, without its subtasks
+    /// Views that add to the end of an item's line use this, and then [`Task::subtask_lines`].
+    pub fn line(&self) -> String {
         let mut description = match self.priority {
             Some(value) => match value {
-                1 => format!("{}", self.description),
+                1 => self.description.to_string(),
                 2 => format!("{} (!)", self.description.yellow()),
                 3 => format!("{} (!!)", self.description.red()),
-                _ => panic!("Task {} has invalid priority", self._id),
+                _ => self.description.to_string(),
             },
-            None => format!("{}", self.description),
+            None => self.description.to_string(),
         };
 
         let mut status = "☐".cyan();
-        match self.inProgress {
-            Some(value) => match value {
-                true => {
-                    status = "∴".blue();
-                }
-                false => (),
-            },
-            None => (),
-        };
+        if self.inProgress == Some(true) {
+            status = "∴".blue();
+        }
 
-        match self.isComplete {
-            Some(value) => match value {
-                true => {
-                    status = "✓".green();
-                    description = format!("{}", self.description.dimmed());
-                }
-                false => (),
-            },
-            None => (),
-        };
+        if self.isComplete == Some(true) {
+            status = "✓".green();
+            description = format!("{}", self.description.dimmed());
+        }
 
         if self.is_note() {
             status = "●".blue();
         }
 
-        let started;
-        if self.isStarred {
-            started = "٭".yellow();
+        let started = if self.isStarred {
+            "٭".yellow()
         } else {
-            started = "".yellow();
-        }
+            "".yellow()
+        };
 
         let millisecond_difference = Local::now().timestamp_millis() - self._timestamp;
         let days = millisecond_difference / 86400000;
@@ -204,11 +547,83 @@ impl fmt::Display for Task {
 
         // let days_since = ()
 
+        let progress = if self.taskerSubtasks.is_empty() {
+            "".dimmed()
+        } else {
+            let (done, total) = self.subtask_progress();
+            format!("[{}/{}] ", done, total).dimmed()
+        };
+
+        let blocked = if self.taskerBlockedBy.is_empty() {
+            "".red()
+        } else {
+            let ids: Vec<String> = self.taskerBlockedBy.iter().map(u64::to_string).collect();
+            format!("blocked by {} ", ids.join(", ")).red()
+        };
+
+        // Running tasks show how long they've been going, the rest how long they took
+        let now = Local::now().timestamp_millis();
+        let timer = match self.taskerSessions.last() {
+            Some(session) if self.in_progress() && session.end.is_none() => {
+                format!("⏱ {} ", format_duration(session.length(now))).blue()
+            }
+            Some(_) => format!("{} ", format_duration(self.tracked_time(now))).dimmed(),
+            None => "".dimmed(),
+        };
+
+        let estimate = match self.taskerEstimate {
+            Some(minutes) => format!("est {} ", format_duration(i64::from(minutes) * 60_000)),
+            None => String::new(),
+        };
+
+        let repeats = if self.taskerRepeat.is_some() {
+            "↻ ".dimmed()
+        } else {
+            "".dimmed()
+        };
+
         let num = format!("{}.", self._id).dimmed();
-        write!(
-            f,
-            "{} {} {} {}{}",
-            num, status, description, days_since, started
+        format!(
+            "{} {} {} {}{}{}{}{}{}{}{}",
+            num,
+            status,
+            description,
+            progress,
+            blocked,
+            self.due_label(),
+            timer,
+            estimate.dimmed(),
+            repeats,
+            days_since,
+            started
         )
     }
+
+    /// # The subtasks, each on a new line
+    /// They go underneath the item, lined up with the description of a parent indented the
+    /// way the views indent items.
+    pub fn subtask_lines(&self) -> String {
+        let mut output = String::new();
+
+        let indent = " ".repeat(8 + self._id.to_string().len());
+        for (index, subtask) in self.taskerSubtasks.iter().enumerate() {
+            let number = format!("{}.{}", self._id, index + 1).dimmed();
+            let (status, description) = if subtask.isComplete {
+                ("✓".green(), subtask.description.dimmed())
+            } else {
+                ("☐".cyan(), subtask.description.normal())
+            };
+            output = format!(
+                "{}\n{}{} {} {}",
+                output, indent, number, status, description
+            );
+        }
+        output
+    }
+}
+
+impl fmt::Display for Task {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}{}", self.line(), self.subtask_lines())
+    }
 }
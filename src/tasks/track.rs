@@ -0,0 +1,319 @@
+use std::collections::BTreeMap;
+
+use chrono::{Local, NaiveDate, TimeZone};
+use colored::*;
+
+use super::due::parse_since;
+use super::*;
+
+/// # Formats a length of time for people
+/// Milliseconds are shown as hours and minutes, like `2h 05m`, or just `12m` under an hour.
+pub fn format_duration(milliseconds: i64) -> String {
+    let minutes = milliseconds / 60_000;
+
+    if minutes >= 60 {
+        format!("{}h {:02}m", minutes / 60, minutes % 60)
+    } else {
+        format!("{}m", minutes)
+    }
+}
+
+/// # A task and how long was spent on it
+struct Entry<'a> {
+    task: &'a Task,
+    time: i64,
+    archived: bool,
+}
+
+impl TaskList {
+    /// # Sums up the time spent on tasks, for timesheets
+    /// Both this list and the archive are counted, grouped by board like the board view.
+    /// With `since`, a day like `monday` or `2022-03-01`, only the time from the start of that
+    /// day on is counted, so sessions that began before it count in part.
+    pub fn time_report(
+        &self,
+        archive: &TaskList,
+        since: Option<&str>,
+    ) -> Result<String, TaskerError> {
+        let since: Option<NaiveDate> = parse_since_phrase(since)?;
+        let from = start_of(since);
+        let now = Local::now().timestamp_millis();
+
+        let lists = [(self, false), (archive, true)];
+        let entries: Vec<Entry> = lists
+            .iter()
+            .flat_map(|(list, archived)| list.tasks.values().map(move |task| (task, *archived)))
+            .map(|(task, archived)| Entry {
+                task,
+                time: time_between(task, from, now),
+                archived,
+            })
+            .filter(|entry| entry.time > 0)
+            .collect();
+
+        let period = describe_period(since);
+        if entries.is_empty() {
+            return Ok(format!(" {} No time tracked{}", "!".yellow(), period));
+        }
+
+        let mut boards: BTreeMap<String, Vec<&Entry>> = BTreeMap::new();
+        for entry in &entries {
+            for board in entry.task.get_boards() {
+                boards.entry(board).or_default().push(entry);
+            }
+        }
+
+        // The default board goes first, like it does in the board view
+        let mut order: Vec<&String> = boards.keys().collect();
+        order.sort_by_key(|board| *board != "My Board");
+
+        let mut output = format!(" {}\n", format!("Time tracked{}", period).bold());
+        for board in order {
+            let entries = &boards[board.as_str()];
+            let total: i64 = entries.iter().map(|entry| entry.time).sum();
+            output = format!(
+                "{} {} {}",
+                output,
+                board.underline(),
+                format_duration(total).dimmed()
+            );
+
+            for entry in entries {
+                let archived = if entry.archived { " (archived)" } else { "" };
+                output = format!(
+                    "{}\n    {} {} {}{}",
+                    output,
+                    format!("{}.", entry.task.get_id()).dimmed(),
+                    entry.task.get_description(),
+                    format_duration(entry.time).blue(),
+                    archived.dimmed()
+                );
+            }
+            output = format!("{}\n", output);
+        }
+
+        let total: i64 = entries.iter().map(|entry| entry.time).sum();
+        Ok(format!(
+            "{}\n  Total: {}",
+            output,
+            format_duration(total).green()
+        ))
+    }
+
+    /// # Compares estimates with the time completed tasks actually took
+    /// Meant for the archive, where finished tasks end up. With `since`, only tasks last worked
+    /// on from that day on are compared.
+    pub fn estimate_report(&self, since: Option<&str>) -> Result<String, TaskerError> {
+        let since: Option<NaiveDate> = parse_since_phrase(since)?;
+        let from = start_of(since);
+        let now = Local::now().timestamp_millis();
+
+        let compared: Vec<(&Task, i64, i64)> = self
+            .tasks
+            .values()
+            .filter(|task| task.is_complete() && finished_at(task) >= from)
+            .filter_map(|task| {
+                let estimate = i64::from(task.get_estimate()?) * 60_000;
+                Some((task, estimate, task.tracked_time(now)))
+            })
+            .collect();
+
+        let period = describe_period(since);
+        if compared.is_empty() {
+            return Ok(format!(
+                " {} No completed tasks with estimates{}",
+                "!".yellow(),
+                period
+            ));
+        }
+
+        let mut output = format!(
+            " {}",
+            format!("Estimates against time taken{}", period).bold()
+        );
+        for (task, estimate, actual) in &compared {
+            output = format!(
+                "{}\n    {} {} {} {} {}",
+                output,
+                format!("{}.", task.get_id()).dimmed(),
+                task.get_description(),
+                format!("est {}", format_duration(*estimate)).dimmed(),
+                format!("took {}", format_duration(*actual)).blue(),
+                difference(*estimate, *actual)
+            );
+        }
+
+        let estimated: i64 = compared.iter().map(|(_, estimate, _)| estimate).sum();
+        let actual: i64 = compared.iter().map(|(_, _, actual)| actual).sum();
+        Ok(format!(
+            "{}\n\n  Total: est {}, took {} {}",
+            output,
+            format_duration(estimated),
+            format_duration(actual),
+            difference(estimated, actual)
+        ))
+    }
+}
+
+/// # Reads an estimate like `2h`, `90m`, `1h30m` or `1.5h`
+/// Gives back the number of minutes, or None if it isn't an estimate.
+pub fn parse_estimate(estimate: &str) -> Option<u32> {
+    let estimate = estimate.trim().to_lowercase();
+    let mut minutes: f64 = 0.0;
+    let mut number = String::new();
+
+    for character in estimate.chars() {
+        match character {
+            '0'..='9' | '.' => number.push(character),
+            'h' | 'm' if !number.is_empty() => {
+                let value: f64 = number.parse().ok()?;
+                minutes += if character == 'h' {
+                    value * 60.0
+                } else {
+                    value
+                };
+                number.clear();
+            }
+            _ => return None,
+        }
+    }
+
+    if !number.is_empty() || minutes < 1.0 {
+        return None;
+    }
+    Some(minutes.round() as u32)
+}
+
+fn parse_since_phrase(since: Option<&str>) -> Result<Option<NaiveDate>, TaskerError> {
+    match since {
+        Some(phrase) => parse_since(phrase, Local::now().date_naive())
+            .map(Some)
+            .ok_or_else(|| TaskerError::InvalidDate(phrase.to_string())),
+        None => Ok(None),
+    }
+}
+
+/// The start of the given day, or of time itself when there isn't one.
+fn start_of(day: Option<NaiveDate>) -> i64 {
+    day.and_then(|day| {
+        Local
+            .from_local_datetime(&day.and_hms_opt(0, 0, 0)?)
+            .earliest()
+    })
+    .map(|start| start.timestamp_millis())
+    .unwrap_or(i64::MIN)
+}
+
+fn describe_period(since: Option<NaiveDate>) -> String {
+    match since {
+        Some(day) => format!(" since {}", day.format("%a %b %-d %Y")),
+        None => String::new(),
+    }
+}
+
+/// When the task was last worked on, or created if it never was.
+fn finished_at(task: &Task) -> i64 {
+    task.get_sessions()
+        .iter()
+        .filter_map(|session| session.end)
+        .max()
+        .unwrap_or_else(|| task.get_timestamp())
+}
+
+/// How far off an estimate was, red when it ran over and green when it didn't.
+fn difference(estimate: i64, actual: i64) -> ColoredString {
+    let percent = (actual - estimate) * 100 / estimate.max(1);
+
+    if percent > 0 {
+        format!("+{}%", percent).red()
+    } else {
+        format!("{}%", percent).green()
+    }
+}
+
+/// How much of the task's sessions fall between `from` and `to`.
+fn time_between(task: &Task, from: i64, to: i64) -> i64 {
+    task.get_sessions()
+        .iter()
+        .map(|session| {
+            let start = session.start.max(from);
+            let end = session.end.unwrap_or(to).min(to);
+            (end - start).max(0)
+        })
+        .sum()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::tasks::{item_json, list_json};
+    use serde_json::json;
+
+    #[test]
+    fn test_format_duration() {
+        assert_eq!(format_duration(59_000), "0m");
+        assert_eq!(format_duration(25 * 60_000), "25m");
+        assert_eq!(format_duration(125 * 60_000), "2h 05m");
+    }
+
+    #[test]
+    fn test_parse_estimate() {
+        assert_eq!(parse_estimate("2h"), Some(120));
+        assert_eq!(parse_estimate("90m"), Some(90));
+        assert_eq!(parse_estimate("1h30m"), Some(90));
+        assert_eq!(parse_estimate("1.5H"), Some(90));
+        assert_eq!(parse_estimate("2"), None);
+        assert_eq!(parse_estimate("soon"), None);
+    }
+
+    #[test]
+    fn test_estimate_report() {
+        let item = |id: u64, complete: bool, estimate: Option<u64>, worked: i64| {
+            let mut fields = json!({
+                "isComplete": complete,
+                "taskerSessions": [{"start": 0, "end": worked}]
+            });
+            if let Some(estimate) = estimate {
+                fields["taskerEstimate"] = json!(estimate);
+            }
+            item_json(id, fields)
+        };
+        let items = [
+            item(0, true, Some(60), 90 * 60_000),
+            item(1, true, Some(120), 60 * 60_000),
+            item(2, false, Some(30), 60_000),
+            item(3, true, None, 60_000),
+        ];
+        let archive = TaskList::new(list_json(items)).unwrap();
+
+        let report = archive.estimate_report(None).unwrap();
+        assert!(report.contains("Item 0") && report.contains("Item 1"));
+        assert!(!report.contains("Item 2") && !report.contains("Item 3"));
+        assert!(report.contains(&"took 1h 30m".blue().to_string()));
+        assert!(report.contains(&"+50%".red().to_string()));
+        assert!(report.contains(&"-50%".green().to_string()));
+        assert!(report.contains("Total: est 3h 00m, took 2h 30m"));
+        assert!(report.contains(&"-16%".green().to_string()));
+
+        let empty = TaskList::new(String::new()).unwrap();
+        let report = empty.estimate_report(None).unwrap();
+        assert!(report.contains("No completed tasks with estimates"));
+    }
+
+    #[test]
+    fn test_sessions_are_clipped_to_the_report() {
+        let item = item_json(
+            0,
+            json!({
+                "inProgress": true,
+                "taskerSessions": [{"start": 1000, "end": 5000}, {"start": 8000}]
+            }),
+        );
+        let list = TaskList::new(list_json([item])).unwrap();
+        let task = &list.tasks[&0];
+
+        assert_eq!(time_between(task, 0, 10_000), 6000);
+        assert_eq!(time_between(task, 4000, 9000), 2000);
+        assert_eq!(task.tracked_time(10_000), 6000);
+    }
+}
@@ -0,0 +1,192 @@
+use std::collections::BTreeMap;
+use std::fmt;
+
+use super::*;
+
+/// # Something wrong with a stored item
+/// These come from hand edits or other programs writing the files, and are what
+/// [`TaskList::validate`] looks for after loading a list.
+#[derive(Debug, PartialEq)]
+pub enum Problem {
+    /// The priority isn't 1, 2, or 3
+    InvalidPriority(u8),
+    /// The `_id` inside the item doesn't match the key it's stored under
+    MismatchedId(u64),
+    /// Another item is stored with the same `_id`
+    DuplicateId(u64),
+    /// A note has a completion state, progress, or priority
+    NoteWithState,
+    /// A task is missing its completion state, progress, or priority
+    TaskWithoutState,
+    /// The item isn't on any board
+    NoBoards,
+    /// The due date isn't an ISO date
+    InvalidDueDate(String),
+    /// The recurrence isn't a rule we know
+    InvalidRecurrence(String),
+    /// The item is blocked by itself, or by an id that isn't in the list
+    UnknownBlocker(u64),
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Problem::InvalidPriority(priority) => {
+                write!(f, "priority {} isn't 1, 2, or 3", priority)
+            }
+            Problem::MismatchedId(id) => write!(f, "stored with the id {}", id),
+            Problem::DuplicateId(id) => write!(f, "shares the id {} with another item", id),
+            Problem::NoteWithState => write!(f, "is a note, but has a completion state"),
+            Problem::TaskWithoutState => write!(f, "is a task, but has no completion state"),
+            Problem::NoBoards => write!(f, "isn't on any board"),
+            Problem::InvalidDueDate(due) => write!(f, "is due {:?}, which isn't a date", due),
+            Problem::UnknownBlocker(id) => write!(f, "is blocked by {}, which isn't here", id),
+            Problem::InvalidRecurrence(rule) => {
+                write!(f, "repeats {:?}, which isn't a rule", rule)
+            }
+        }
+    }
+}
+
+/// # A problem and the key of the item it was found in
+#[derive(Debug, PartialEq)]
+pub struct Issue {
+    pub id: u64,
+    pub problem: Problem,
+}
+
+impl TaskList {
+    /// # Checks every item in the list for problems
+    /// Every problem is reported, not just the first, ordered by the key of the item.
+    pub fn validate(&self) -> Vec<Issue> {
+        let mut issues: Vec<Issue> = Vec::new();
+        let mut seen_ids: BTreeMap<u64, u64> = BTreeMap::new();
+
+        for (key, task) in &self.tasks {
+            if let Some(first) = seen_ids.insert(task.get_id(), *key) {
+                issues.push(Issue {
+                    id: first,
+                    problem: Problem::DuplicateId(task.get_id()),
+                });
+            }
+
+            if task.get_id() != *key {
+                issues.push(Issue {
+                    id: *key,
+                    problem: Problem::MismatchedId(task.get_id()),
+                });
+            }
+
+            for problem in task.problems() {
+                issues.push(Issue { id: *key, problem });
+            }
+
+            for blocker in task.get_blockers() {
+                if blocker == key || !self.tasks.contains_key(blocker) {
+                    issues.push(Issue {
+                        id: *key,
+                        problem: Problem::UnknownBlocker(*blocker),
+                    });
+                }
+            }
+        }
+
+        issues.sort_by_key(|issue| issue.id);
+        issues
+    }
+
+    /// # Fixes every problem [`TaskList::validate`] finds
+    /// Items take the id of the key they're stored under, which also sorts out duplicates,
+    /// links to items that aren't there are dropped, and everything else is reset to what a
+    /// new item would have.
+    pub fn repair(&mut self) {
+        let keys: Vec<u64> = self.tasks.keys().copied().collect();
+        for (key, task) in self.tasks.iter_mut() {
+            task.set_id(*key);
+            task.repair();
+
+            let blockers: Vec<u64> = task.get_blockers().to_vec();
+            for blocker in blockers {
+                if blocker == *key || !keys.contains(&blocker) {
+                    task.remove_blocker(blocker);
+                }
+            }
+        }
+    }
+
+    /// # Rebuilds a list from a file that doesn't load as a whole
+    /// When the file is still a JSON object, every item that can be read is kept, and the ones
+    /// that can't are taken from `backup` when it has an item under the same key. Gives back
+    /// the list along with a note for every item that had to be replaced or dropped, or None
+    /// if there was nothing to salvage.
+    pub fn salvage(contents: &str, backup: Option<&TaskList>) -> Option<(TaskList, Vec<String>)> {
+        let records: serde_json::Map<String, serde_json::Value> =
+            serde_json::from_str(contents).ok()?;
+        let mut tasks: BTreeMap<u64, Task> = BTreeMap::new();
+        let mut notes: Vec<String> = Vec::new();
+
+        for (key, record) in records {
+            let id: u64 = match key.parse() {
+                Ok(id) => id,
+                Err(_) => {
+                    notes.push(format!("dropped the item under {:?}, it isn't an id", key));
+                    continue;
+                }
+            };
+
+            let error = match serde_json::from_value::<Task>(record) {
+                Ok(task) => {
+                    tasks.insert(id, task);
+                    continue;
+                }
+                Err(error) => error,
+            };
+
+            match backup.and_then(|backup| backup.tasks.get(&id)) {
+                Some(task) => {
+                    tasks.insert(id, task.clone());
+                    notes.push(format!("took item {} from the backup: {}", id, error));
+                }
+                None => notes.push(format!("dropped item {}: {}", id, error)),
+            }
+        }
+
+        Some((TaskList { tasks }, notes))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::tasks::item_json;
+    use serde_json::json;
+
+    #[test]
+    fn test_reports_every_problem() {
+        let json = json!({
+            "0": item_json(0, json!({})),
+            "1": item_json(0, json!({"boards": [], "priority": 9})),
+            "2": item_json(2, json!({"_isTask": false, "isComplete": true})),
+        });
+        let mut list = TaskList::new(json.to_string()).unwrap();
+
+        let problems: Vec<(u64, Problem)> = list
+            .validate()
+            .into_iter()
+            .map(|issue| (issue.id, issue.problem))
+            .collect();
+        assert_eq!(
+            problems,
+            vec![
+                (0, Problem::DuplicateId(0)),
+                (1, Problem::MismatchedId(0)),
+                (1, Problem::InvalidPriority(9)),
+                (1, Problem::NoBoards),
+                (2, Problem::NoteWithState),
+            ]
+        );
+
+        list.repair();
+        assert!(list.validate().is_empty());
+    }
+}
@@ -1,65 +1,349 @@
 use std::collections::BTreeMap;
+use std::fmt;
 
+use chrono::Local;
 use colored::*;
 
+use super::track::format_duration;
 use super::*;
+use crate::global_settings::Settings;
 
 impl TaskList {
-    pub fn board_view(&self) -> String {
-        let mut output: String = String::new();
-        let mut boards: BTreeMap<String, (u16, u16)> = BTreeMap::new();
-
-        // Create a set of boards connected to the tuple of (completed, total)
-        for (_, ref task) in &self.tasks {
-            for board_name in &task.get_boards() {
-                // Since not everything is a task, not everything can be completed,
-                // so we set the value to 1 if complete and 0 for all other cases
-                let is_complete: u16 = task.is_complete().into();
-                let key_check = boards.clone();
-                // Update the tuple or create a tuple if haven't encountered this board yet
-                if key_check.contains_key(board_name) {
-                    let total_tuple = key_check.get(board_name).unwrap();
-                    boards.insert(
-                        board_name.clone(),
-                        (total_tuple.0 + is_complete, total_tuple.1 + 1),
-                    );
-                } else {
-                    boards.insert(board_name.clone(), (is_complete, 1));
-                }
+    pub fn board_view(&self, settings: &Settings) -> String {
+        let tasks: Vec<&Task> = self.tasks.values().collect();
+        let output = render_boards(&tasks, &[], settings.show_completed());
+        with_overview(output, &tasks, settings)
+    }
+
+    /// # Shows the archive by board
+    /// Everything in the archive is shown, whether `displayCompleteTasks` is on or not.
+    pub fn archive_view(&self, settings: &Settings) -> String {
+        let tasks: Vec<&Task> = self.tasks.values().collect();
+        with_overview(render_boards(&tasks, &[], true), &tasks, settings)
+    }
+
+    /// # Lists the items by the day they were created
+    /// Days are shown newest first, each with the same `[completed/total]` progress the boards
+    /// get, and every item is followed by the boards it's on. Completed items still count
+    /// towards the progress when `displayCompleteTasks` hides them.
+    pub fn timeline_view(&self, settings: &Settings) -> String {
+        let mut days: Vec<(&str, Vec<&Task>)> = Vec::new();
+
+        let mut tasks: Vec<&Task> = self.tasks.values().collect();
+        tasks.sort_by_key(|task| std::cmp::Reverse(task.get_timestamp()));
+
+        for task in tasks {
+            match days.iter_mut().find(|(date, _)| *date == task.get_date()) {
+                Some((_, day)) => day.push(task),
+                None => days.push((task.get_date(), vec![task])),
             }
         }
 
-        // Process the default board first
-        if boards.contains_key("My Board") {
-            let (completed, total) = boards.get("My Board").unwrap();
+        let today = Local::now().format("%a %b %e %Y").to_string();
+        let mut output: String = String::new();
 
-            let board_name = "My Board".underline();
-            let progress = format!("[{}/{}]", completed, total).dimmed();
+        for (date, mut day) in days {
+            day.sort_by_key(|task| task.get_id());
 
-            output = format!(" {} {}", board_name, progress);
+            let completed = day.iter().filter(|task| task.is_complete()).count();
+            let progress = format!("[{}/{}]", completed, day.len()).dimmed();
+            let header = if date == today {
+                format!("{} {}", date.underline(), "[Today]".dimmed())
+            } else {
+                date.underline().to_string()
+            };
 
-            for (_, task) in &self.tasks {
-                if task.get_boards().contains(&"My Board".to_string()) {
-                    output = format!("{}\n    {}", output, task);
+            output = format!("{} {} {}", output, header, progress);
+            for task in day {
+                if task.is_complete() && !settings.show_completed() {
+                    continue;
                 }
+                let boards: Vec<String> = task
+                    .get_boards()
+                    .into_iter()
+                    .filter(|board| board != "My Board")
+                    .collect();
+                output = format!(
+                    "{}\n    {} {}{}",
+                    output,
+                    task.line(),
+                    boards.join(" ").dimmed(),
+                    task.subtask_lines()
+                );
             }
             output = format!("{}\n", output);
-            boards.remove("My Board");
-        };
+        }
 
-        // Process over the rest of the boards
-        for (ref board, (completed, total)) in boards {
-            let progress = format!("[{}/{}]", completed, total).dimmed();
+        let tasks: Vec<&Task> = self.tasks.values().collect();
+        with_overview(output, &tasks, settings)
+    }
+}
 
-            output = format!("{} {} {}", output, board.underline(), progress.dimmed());
-            for (_, task) in &self.tasks {
-                if task.get_boards().contains(board) {
-                    output = format!("{}\n    {}", output, task);
-                }
+/// # Adds the progress overview to the end of a view
+/// This is only done when `displayProgressOverview` is turned on. The overview sums up the
+/// items the view is about, which for searches and filters is only the ones that matched.
+pub(super) fn with_overview(output: String, tasks: &[&Task], settings: &Settings) -> String {
+    if settings.show_progress() {
+        format!("{}\n{}", output, progress_overview(tasks))
+    } else {
+        output
+    }
+}
+
+/// # Summarises the progress over the given items
+/// Gives how much of the tasks are complete, and how many items are done, in progress,
+/// pending, or just notes.
+fn progress_overview(tasks: &[&Task]) -> String {
+    let (mut done, mut in_progress, mut pending, mut notes) = (0, 0, 0, 0);
+
+    for task in tasks {
+        if task.is_note() {
+            notes += 1;
+        } else if task.is_complete() {
+            done += 1;
+        } else if task.in_progress() {
+            in_progress += 1;
+        } else {
+            pending += 1;
+        }
+    }
+
+    let total = done + in_progress + pending;
+    let percent = if total == 0 { 0 } else { done * 100 / total };
+    let percent_text = match percent {
+        0..=49 => format!("{}%", percent).red(),
+        50..=74 => format!("{}%", percent).yellow(),
+        _ => format!("{}%", percent).green(),
+    };
+
+    format!(
+        "  {} {}\n  {} {} {} {} {} {} {} {}",
+        percent_text,
+        "of all tasks complete.".dimmed(),
+        done.to_string().green(),
+        "done ·".dimmed(),
+        in_progress.to_string().blue(),
+        "in-progress ·".dimmed(),
+        pending.to_string().magenta(),
+        "pending ·".dimmed(),
+        notes.to_string().blue(),
+        plural(notes, "note").dimmed(),
+    )
+}
+
+fn plural(count: usize, word: &str) -> String {
+    if count == 1 {
+        word.into()
+    } else {
+        format!("{}s", word)
+    }
+}
+
+/// # Renders tasks grouped under their boards
+/// Every board a task belongs to gets a header with its `[completed/total]` progress, and the
+/// time tracked and estimated on it, followed by the tasks on it. The default board is always
+/// printed first, the rest follow in order. When `only` isn't empty, boards missing from it are
+/// left out. Completed tasks always count towards the progress, but are only listed when
+/// `show_completed` is set.
+pub(super) fn render_boards(tasks: &[&Task], only: &[String], show_completed: bool) -> String {
+    let shown: Vec<&&Task> = tasks
+        .iter()
+        .filter(|task| show_completed || !task.is_complete())
+        .collect();
+
+    let mut output: String = String::new();
+    let mut boards: BTreeMap<String, Tally> = BTreeMap::new();
+    let now = Local::now().timestamp_millis();
+
+    // Create a set of boards connected to their tally
+    for task in tasks {
+        for board_name in &task.get_boards() {
+            if !only.is_empty() && !only.contains(board_name) {
+                continue;
             }
-            output = format!("{}\n", output);
+            // Since not everything is a task, not everything can be completed,
+            // so we set the value to 1 if complete and 0 for all other cases
+            let is_complete: u16 = task.is_complete().into();
+            let tally = boards.entry(board_name.clone()).or_default();
+            tally.completed += is_complete;
+            tally.total += 1;
+            tally.tracked += task.tracked_time(now);
+            tally.estimated += i64::from(task.get_estimate().unwrap_or(0)) * 60_000;
         }
+    }
 
-        output
+    // Process the default board first
+    if let Some(tally) = boards.remove("My Board") {
+        let board_name = "My Board".underline();
+        let progress = tally.to_string().dimmed();
+
+        output = format!(" {} {}", board_name, progress);
+
+        for task in &shown {
+            if task.get_boards().contains(&"My Board".to_string()) {
+                output = format!("{}\n    {}", output, task);
+            }
+        }
+        output = format!("{}\n", output);
+    };
+
+    // Process over the rest of the boards
+    for (ref board, tally) in boards {
+        let progress = tally.to_string().dimmed();
+
+        output = format!("{} {} {}", output, board.underline(), progress);
+        for task in &shown {
+            if task.get_boards().contains(board) {
+                output = format!("{}\n    {}", output, task);
+            }
+        }
+        output = format!("{}\n", output);
+    }
+
+    output
+}
+
+/// # What's on a board, for its header
+/// Times are in milliseconds.
+#[derive(Default)]
+struct Tally {
+    completed: u16,
+    total: u16,
+    tracked: i64,
+    estimated: i64,
+}
+
+/// The `[completed/total]` progress, followed by the time tracked and estimated if there's any.
+impl fmt::Display for Tally {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "[{}/{}]", self.completed, self.total)?;
+        if self.tracked > 0 {
+            write!(f, " {}", format_duration(self.tracked))?;
+        }
+        if self.estimated > 0 {
+            write!(f, " est {}", format_duration(self.estimated))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::tasks::{item_json, list_json};
+    use serde_json::json;
+
+    /// The part of the overview counting `count` items of a kind.
+    fn counted(count: usize, kind: &str) -> String {
+        let count = count.to_string();
+        match kind {
+            "done" => format!("{} {}", count.green(), "done ·".dimmed()),
+            "pending" => format!("{} {}", count.magenta(), "pending ·".dimmed()),
+            _ => format!("{} {}", count.blue(), kind.dimmed()),
+        }
+    }
+
+    #[test]
+    fn test_overview_counts_and_percentage() {
+        let mut list = TaskList::new(String::new()).unwrap();
+        let settings = Settings::new(String::new()).unwrap();
+        for description in ["@a One", "@a Two", "@b Three", "@b Four", "@b Five"] {
+            list.new_entry(description.split(' ').collect(), false)
+                .unwrap();
+        }
+        list.new_entry(vec!["@a", "Note"], true).unwrap();
+        for id in [0, 2, 3] {
+            list.tasks.get_mut(&id).unwrap().flip_flag(TaskFlag::Check);
+        }
+        list.tasks.get_mut(&4).unwrap().flip_flag(TaskFlag::Begin);
+
+        let tasks: Vec<&Task> = list.tasks.values().collect();
+        let overview = progress_overview(&tasks);
+        assert!(overview.contains("60%"));
+        assert!(overview.contains(&counted(3, "done")));
+        assert!(overview.contains(&counted(1, "in-progress ·")));
+        assert!(overview.contains(&counted(1, "pending")));
+        assert!(overview.contains(&counted(1, "note")));
+
+        let listed = list.list_view(vec!["@a"], &settings);
+        assert!(listed.contains("50%") && listed.contains(&counted(1, "note")));
+        let found = list.find(vec!["Two"], &settings);
+        assert!(found.contains("0%") && found.contains(&counted(1, "pending")));
+        assert!(found.contains(&counted(0, "done")));
+    }
+
+    #[test]
+    fn test_overview_of_nothing() {
+        let overview = progress_overview(&[]);
+
+        assert!(overview.contains("0%"));
+        assert!(overview.contains(&counted(0, "done")));
+        assert!(overview.contains(&counted(0, "notes")));
+    }
+
+    #[test]
+    fn test_boards_add_up_estimates() {
+        let mut list = TaskList::new(String::new()).unwrap();
+        for (description, estimate) in [("@work One", 30), ("@work Two", 45), ("Three", 20)] {
+            list.new_entry(description.split(' ').collect(), false)
+                .unwrap();
+            let id = list.get_new_id() - 1;
+            list.tasks
+                .get_mut(&id)
+                .unwrap()
+                .set_estimate(Some(estimate));
+        }
+        list.tasks.get_mut(&1).unwrap().flip_flag(TaskFlag::Check);
+        let tasks: Vec<&Task> = list.tasks.values().collect();
+
+        let output = render_boards(&tasks, &[], false);
+
+        assert!(output.contains("[0/1] est 20m"));
+        assert!(output.contains("[1/2] est 1h 15m"));
+    }
+
+    #[test]
+    fn test_timeline_groups_by_day_newest_first() {
+        let item = |id: u64, date: &str, timestamp: i64, complete: bool| {
+            let fields = json!({"_date": date, "_timestamp": timestamp, "isComplete": complete});
+            item_json(id, fields)
+        };
+        let items = [
+            item(0, "Mon Mar 14 2022", 1_000, true),
+            item(1, "Wed Mar 16 2022", 4_000, false),
+            item(2, "Mon Mar 14 2022", 2_000, false),
+            item(3, "Wed Mar 16 2022", 3_000, true),
+            item(4, "Tue Mar 15 2022", 2_500, false),
+        ];
+        let list = TaskList::new(list_json(items)).unwrap();
+        let settings = Settings::new(String::new()).unwrap();
+
+        let output = list.timeline_view(&settings);
+        let lines: Vec<&str> = output.lines().take(8).collect();
+
+        let header =
+            |date: &str, progress: &str| format!(" {} {}", date.underline(), progress.dimmed());
+        assert_eq!(lines[0], header("Wed Mar 16 2022", "[1/2]"));
+        assert!(lines[1].contains("Item 1") && lines[2].contains("Item 3"));
+        assert_eq!(lines[3], header("Tue Mar 15 2022", "[0/1]"));
+        assert!(lines[4].contains("Item 4"));
+        assert_eq!(lines[5], header("Mon Mar 14 2022", "[1/2]"));
+        assert!(lines[6].contains("Item 0") && lines[7].contains("Item 2"));
+    }
+
+    #[test]
+    fn test_timeline_tags_come_before_subtasks() {
+        let mut list = TaskList::new(String::new()).unwrap();
+        let settings = Settings::new(String::new()).unwrap();
+        list.new_entry(vec!["@work", "Ship", "it"], false).unwrap();
+        list.add_subtask(vec!["@0", "Tag"]).unwrap();
+        list.add_subtask(vec!["@0", "Notes"]).unwrap();
+
+        let output = list.timeline_view(&settings);
+        let lines: Vec<&str> = output.lines().collect();
+
+        assert!(lines[1].contains("Ship it") && lines[1].contains("@work"));
+        assert!(lines[2].contains("0.1") && lines[3].contains("0.2"));
+        assert_eq!(output.matches("@work").count(), 1);
     }
 }
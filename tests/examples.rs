@@ -0,0 +1,282 @@
+//! Runs `ts` the way people do, starting with every example `ts --help` gives, so that the
+//! help never shows a command that doesn't work.
+
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output, Stdio};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, SystemTime};
+use std::{env, fs, process, thread};
+
+/// # A home, config and data directory of its own
+/// Everything is removed again once the sandbox is dropped.
+struct Sandbox {
+    root: PathBuf,
+}
+
+impl Sandbox {
+    fn new() -> Sandbox {
+        static COUNT: AtomicUsize = AtomicUsize::new(0);
+        let name = format!(
+            "tasker-test-{}-{}",
+            process::id(),
+            COUNT.fetch_add(1, Ordering::SeqCst)
+        );
+        let root = env::temp_dir().join(name);
+
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(root.join("home")).unwrap();
+        Sandbox { root }
+    }
+
+    /// # A sandbox with a list of items for the examples to work on
+    /// Items 0 to 5 are on `@work` and 6 to 11 on `@coding`. Item 4 has three subtasks and
+    /// item 10 is checked. The archive holds items 0 to 4, and Taskbook has a task in the
+    /// home directory waiting to be imported.
+    fn seeded() -> Sandbox {
+        let sandbox = Sandbox::new();
+
+        for number in 0..17 {
+            let board = if number < 6 { "@work" } else { "@coding" };
+            sandbox.ok(&["--task", board, "Item", &number.to_string()]);
+        }
+        sandbox.ok(&["--check", "12-16"]);
+        sandbox.ok(&["--clear"]);
+        for description in ["Draft", "Review", "Publish"] {
+            sandbox.ok(&["--subtask", "@4", description]);
+        }
+        sandbox.ok(&["--check", "10"]);
+
+        let taskbook = sandbox.home().join(".taskbook").join("storage");
+        fs::create_dir_all(&taskbook).unwrap();
+        // Written out by hand, since `item_json` in the crate's tests isn't reachable from here
+        let item = r#"{"0": {"_id": 0, "_date": "Mon Mar 14 2022", "_timestamp": 0,
+            "description": "From Taskbook", "isStarred": false, "boards": ["My Board"],
+            "_isTask": true, "isComplete": false, "inProgress": false, "priority": 1}}"#;
+        fs::write(taskbook.join("storage.json"), item).unwrap();
+
+        sandbox
+    }
+
+    /// A new sandbox holding the same files as this one.
+    fn copy(&self) -> Sandbox {
+        let copy = Sandbox::new();
+        copy_dir(&self.root, &copy.root);
+        copy
+    }
+
+    fn home(&self) -> PathBuf {
+        self.root.join("home")
+    }
+
+    fn command(&self, args: &[&str]) -> Command {
+        let mut command = Command::new(env!("CARGO_BIN_EXE_tasker"));
+        command
+            .args(args)
+            .env("HOME", self.home())
+            .env("XDG_CONFIG_HOME", self.root.join("config"))
+            .env("TASKER_DIR", self.root.join("data"))
+            .env("TASKER_CLIPBOARD", "stdout")
+            .env("NO_COLOR", "1");
+        command
+    }
+
+    fn run(&self, args: &[&str]) -> Output {
+        self.command(args).stdin(Stdio::null()).output().unwrap()
+    }
+
+    /// Runs `ts`, typing `input` when it asks something.
+    fn answer(&self, args: &[&str], input: &str) -> Output {
+        let mut child = self
+            .command(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .unwrap();
+        child
+            .stdin
+            .take()
+            .unwrap()
+            .write_all(input.as_bytes())
+            .unwrap();
+        child.wait_with_output().unwrap()
+    }
+
+    /// Runs `ts`, failing the test unless it succeeds, and gives back what it printed.
+    fn ok(&self, args: &[&str]) -> String {
+        let output = self.run(args);
+        assert!(
+            output.status.success(),
+            "`ts {}` failed with {}: {}",
+            args.join(" "),
+            output.status,
+            String::from_utf8_lossy(&output.stderr)
+        );
+        String::from_utf8_lossy(&output.stdout).into_owned()
+    }
+}
+
+impl Drop for Sandbox {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.root);
+    }
+}
+
+fn copy_dir(from: &Path, to: &Path) {
+    fs::create_dir_all(to).unwrap();
+    for entry in fs::read_dir(from).unwrap() {
+        let entry = entry.unwrap();
+        let target = to.join(entry.file_name());
+        if entry.file_type().unwrap().is_dir() {
+            copy_dir(&entry.path(), &target);
+        } else {
+            fs::copy(entry.path(), target).unwrap();
+        }
+    }
+}
+
+/// Every file under `directory` along with when it was last changed.
+fn modified_times(directory: &Path) -> Vec<(PathBuf, SystemTime)> {
+    let mut times = Vec::new();
+    for entry in fs::read_dir(directory).unwrap() {
+        let entry = entry.unwrap();
+        if entry.file_type().unwrap().is_dir() {
+            times.extend(modified_times(&entry.path()));
+        } else {
+            times.push((entry.path(), entry.metadata().unwrap().modified().unwrap()));
+        }
+    }
+    times.sort();
+    times
+}
+
+/// The examples at the end of `ts --help`, without the `$ ts` in front of them.
+fn help_examples(sandbox: &Sandbox) -> Vec<String> {
+    let help = sandbox.ok(&["--help"]);
+    let (_, examples) = help.split_once("EXAMPLES:").unwrap();
+
+    examples
+        .lines()
+        .filter_map(|line| line.trim().strip_prefix("$ ts"))
+        .map(|example| example.trim().to_string())
+        .collect()
+}
+
+#[test]
+fn test_every_help_example_works() {
+    let seeded = Sandbox::seeded();
+    let examples = help_examples(&seeded);
+    assert!(examples.len() > 30);
+
+    for example in examples {
+        let sandbox = seeded.copy();
+        // The shell would expand these before we see them
+        let home = sandbox.home().display().to_string();
+        let args: Vec<&str> = example
+            .split_whitespace()
+            .map(|word| if word == "~" { home.as_str() } else { word })
+            .collect();
+
+        sandbox.ok(&args);
+    }
+}
+
+#[test]
+fn test_commands_take_several_ids() {
+    let sandbox = Sandbox::seeded();
+
+    sandbox.ok(&["--check", "1", "2"]);
+    sandbox.ok(&["--star", "1", "3"]);
+    let done = sandbox.ok(&["--list", "done"]);
+    assert!(done.contains("Item 1") && done.contains("Item 2"));
+    let starred = sandbox.ok(&["--list", "starred"]);
+    assert!(starred.contains("Item 1") && starred.contains("Item 3"));
+
+    let output = sandbox.ok(&["--delete", "1", "2"]);
+    assert!(output.contains("Deleted item(s): 1, 2"));
+    let output = sandbox.ok(&["--restore", "5", "6"]);
+    assert!(output.contains("Restored item(s): 5, 6"));
+}
+
+#[test]
+fn test_ids_can_follow_repeated_flags() {
+    let sandbox = Sandbox::seeded();
+
+    sandbox.ok(&["-b", "0", "-b", "1"]);
+    sandbox.ok(&["--star", "2", "--star", "3,5"]);
+
+    let started = sandbox.ok(&["--list", "started"]);
+    assert!(started.contains("Item 0") && started.contains("Item 1"));
+    let starred = sandbox.ok(&["--list", "starred"]);
+    assert!(["Item 2", "Item 3", "Item 5"]
+        .iter()
+        .all(|item| starred.contains(item)));
+}
+
+#[test]
+fn test_commands_take_several_words() {
+    let sandbox = Sandbox::new();
+
+    sandbox.ok(&["--task", "@coding", "Finish", "something", "for", "once"]);
+    sandbox.ok(&["--note", "@coding", "Actually", "learn", "rust"]);
+    sandbox.ok(&["--edit", "@0", "Merge", "PR", "#42"]);
+    sandbox.ok(&["--move", "@1", "cooking", "reading"]);
+    sandbox.ok(&["--priority", "@0", "2"]);
+
+    let board = sandbox.ok(&[]);
+    assert!(board.contains("Merge PR #42 (!)"));
+    assert!(board.contains("@cooking") && board.contains("@reading"));
+    assert!(board.contains("Actually learn rust"));
+
+    let output = sandbox.run(&["--priority", "@0", "2", "3"]);
+    assert_eq!(output.status.code(), Some(6));
+}
+
+#[test]
+fn test_views_leave_files_alone() {
+    let sandbox = Sandbox::seeded();
+    let before = modified_times(&sandbox.root);
+    // Long enough for any write to show up in the times
+    thread::sleep(Duration::from_millis(50));
+
+    for view in [
+        vec![],
+        vec!["--archive"],
+        vec!["--timeline"],
+        vec!["--find", "Item"],
+        vec!["--list", "done"],
+        vec!["report", "time"],
+        vec!["report", "estimates"],
+    ] {
+        sandbox.ok(&view);
+        assert_eq!(
+            modified_times(&sandbox.root),
+            before,
+            "`ts {}`",
+            view.join(" ")
+        );
+    }
+}
+
+#[test]
+fn test_big_selections_need_a_yes() {
+    let sandbox = Sandbox::seeded();
+
+    for answer in ["\n", "n\n"] {
+        let output = sandbox.answer(&["--delete", "0-11"], answer);
+        assert_eq!(output.status.code(), Some(16));
+        assert!(String::from_utf8_lossy(&output.stderr).contains("[y/N]"));
+    }
+    assert!(sandbox.ok(&[]).contains("Item 0"));
+
+    // Ids given one by one are never asked about, however they're joined
+    let ids: Vec<String> = (0..12).map(|id| id.to_string()).collect();
+    sandbox.copy().ok(&["--delete", &ids.join(",")]);
+    let ids: Vec<&str> = ids.iter().map(String::as_str).collect();
+    sandbox.copy().ok(&[&["--delete"], ids.as_slice()].concat());
+
+    let output = sandbox.answer(&["--delete", "0-11"], "y\n");
+    assert!(output.status.success());
+    assert!(!sandbox.ok(&[]).contains("Item 0"));
+}